@@ -0,0 +1,394 @@
+// builder.rs
+
+use crate::{
+    checksum::{Checksum, ChecksumAlgorithm},
+    control::DownloadHandle,
+    downloader::{Downloader, ProgressTracker, SinkState},
+    rate_limit::RateLimiter,
+    redact,
+    redirect::{self, RedirectPolicy},
+    retry::{ExponentialBackoff, RetryPolicy},
+    sink::Sink,
+    stall::{MinThroughput, StallConfig},
+    transport::TransportConfig,
+    writer::DEFAULT_WRITE_BUFFER,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, USER_AGENT};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Configures a [`Downloader`] that owns all of its inputs and can be moved into `tokio::spawn`
+pub struct DownloaderBuilder {
+    url: String,
+    mirrors: Vec<String>,
+    parallel_mirrors: bool,
+    output_path: PathBuf,
+    title: Option<String>,
+    progress: Option<ProgressTracker>,
+    segments: usize,
+    client: Option<reqwest::Client>,
+    checksums: Vec<Checksum>,
+    digest_algorithms: Vec<ChecksumAlgorithm>,
+    retry_policy: Option<Arc<dyn RetryPolicy>>,
+    redirect_policy: RedirectPolicy,
+    stall: StallConfig,
+    rate_limiters: Vec<Arc<RateLimiter>>,
+    control: Option<DownloadHandle>,
+    durable: bool,
+    write_buffer: usize,
+    preallocate: bool,
+    /// `output_path` is a directory and the file name comes from the response
+    resolve_name: bool,
+    sink: Option<Box<dyn Sink>>,
+    headers: HeaderMap,
+    /// A setting that could not be applied, reported by the first download
+    config_error: Option<String>,
+}
+
+impl DownloaderBuilder {
+    pub fn new(url: impl Into<String>, output_path: impl AsRef<Path>) -> Self {
+        Self {
+            url: url.into(),
+            mirrors: Vec::new(),
+            parallel_mirrors: false,
+            output_path: output_path.as_ref().to_path_buf(),
+            title: None,
+            progress: None,
+            segments: 1,
+            client: None,
+            checksums: Vec::new(),
+            digest_algorithms: Vec::new(),
+            retry_policy: None,
+            redirect_policy: RedirectPolicy::default(),
+            stall: StallConfig::default(),
+            rate_limiters: Vec::new(),
+            control: None,
+            durable: false,
+            write_buffer: DEFAULT_WRITE_BUFFER,
+            preallocate: false,
+            resolve_name: false,
+            sink: None,
+            headers: HeaderMap::new(),
+            config_error: None,
+        }
+    }
+
+    /// Downloads into `dir` under a name taken from the response.
+    ///
+    /// The name comes from `Content-Disposition`, or else the last segment of the URL after
+    /// redirects, and is sanitized so it cannot leave `dir`. See [`Downloader::output_path`].
+    pub fn in_dir(url: impl Into<String>, dir: impl AsRef<Path>) -> Self {
+        let mut builder = Self::new(url, dir);
+        builder.resolve_name = true;
+        builder
+    }
+
+    /// Downloads into `sink` instead of a file.
+    ///
+    /// Nothing is kept on disk, so only retries within this download can resume: they continue
+    /// after the bytes the sink holds, or start over if the sink can discard them. Segments,
+    /// preallocation and durability apply to files and are ignored.
+    pub fn to_sink(url: impl Into<String>, sink: impl Sink + 'static) -> Self {
+        let mut builder = Self::new(url, PathBuf::new());
+        builder.sink = Some(Box::new(sink));
+        builder
+    }
+
+    /// Fallback URL serving the same file, tried in the order added.
+    ///
+    /// When the active mirror errors, stalls or answers 404, the download continues from the
+    /// next mirror, as long as it reports the same size and validators as the part file.
+    pub fn mirror(mut self, url: impl Into<String>) -> Self {
+        self.mirrors.push(url.into());
+        self
+    }
+
+    pub fn mirrors<I, S>(self, urls: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        urls.into_iter()
+            .fold(self, |builder, url| builder.mirror(url))
+    }
+
+    /// Fetches ranges from every mirror at the same time instead of keeping them as fallbacks.
+    ///
+    /// Mirrors that report a different length are left out. Faster mirrors serve more of the
+    /// file, and ranges held by a much slower or failing mirror move to the others.
+    pub fn parallel_mirrors(mut self, parallel: bool) -> Self {
+        self.parallel_mirrors = parallel;
+        self
+    }
+
+    /// Label shown in progress lines; defaults to the output file name
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn progress(mut self, progress: ProgressTracker) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Splits the file into `segments` byte ranges fetched over concurrent connections.
+    ///
+    /// Falls back to a single stream when the server does not honor range requests.
+    pub fn segments(mut self, segments: usize) -> Self {
+        self.segments = segments.max(1);
+        self
+    }
+
+    /// Client used for the probe, the download and every retry.
+    ///
+    /// Pass a clone of one client to many downloaders to share its connection pool. Build it with
+    /// [`TransportConfig`], or at least with `redirect::Policy::none()`: redirects are left to the
+    /// [`RedirectPolicy`], and a client that follows one itself fails the download.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Expected digest of the complete file; may be given once per algorithm.
+    ///
+    /// On mismatch the part file is quarantined instead of being renamed into place.
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.digest_algorithms.push(checksum.algorithm);
+        self.checksums.push(checksum);
+        self
+    }
+
+    /// Computes a digest without verifying it, see [`Downloader::digests`]
+    pub fn digest(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.digest_algorithms.push(algorithm);
+        self
+    }
+
+    /// Policy for retrying failed attempts; defaults to [`ExponentialBackoff::default`]
+    pub fn retry_policy(mut self, policy: impl RetryPolicy + 'static) -> Self {
+        self.retry_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Redirects to follow on the way to the file; defaults to [`RedirectPolicy::default`]
+    pub fn redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = policy;
+        self
+    }
+
+    /// Aborts the attempt, and resumes through the retry policy, after `timeout` without data
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.stall.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Aborts the attempt, and resumes through the retry policy, when fewer than
+    /// `bytes_per_second` arrive on average over any `window`
+    pub fn min_throughput(mut self, bytes_per_second: u64, window: Duration) -> Self {
+        self.stall.min_throughput = Some(MinThroughput {
+            bytes_per_second,
+            window,
+        });
+        self
+    }
+
+    /// Caps this download at `bytes_per_second`
+    pub fn rate_limit(self, bytes_per_second: u64) -> Self {
+        self.rate_limiter(Arc::new(RateLimiter::new(bytes_per_second)))
+    }
+
+    /// Draws from `limiter` as well as any other configured limiter.
+    ///
+    /// Share one limiter between downloads to cap their aggregate rate, and keep a clone of the
+    /// `Arc` to adjust it while they run.
+    pub fn rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiters.push(limiter);
+        self
+    }
+
+    /// Size of the buffer that coalesces received chunks into larger disk writes, one per
+    /// connection
+    pub fn write_buffer(mut self, bytes: usize) -> Self {
+        self.write_buffer = bytes;
+        self
+    }
+
+    /// Reserves the full size of the part file up front.
+    ///
+    /// Requires range support: the download runs as one segment whose progress is recorded in
+    /// the part metadata. Servers without ranges are downloaded as a growing stream instead.
+    pub fn preallocate(mut self, preallocate: bool) -> Self {
+        self.preallocate = preallocate;
+        self
+    }
+
+    /// Syncs the part file before renaming it and the directory after, so the final file is
+    /// complete on disk once `download()` returns, even across a power loss
+    pub fn durable(mut self, durable: bool) -> Self {
+        self.durable = durable;
+        self
+    }
+
+    /// Sends `name: value` with the probe, the download and every retry, replacing earlier values.
+    ///
+    /// Headers that look like credentials, such as `X-API-Key`, are never logged and are not sent
+    /// to another origin after a redirect; [`DownloaderBuilder::credential_header`] does the same
+    /// for any other name.
+    pub fn header(self, name: HeaderName, mut value: HeaderValue) -> Self {
+        if redirect::is_credential(&name) {
+            value.set_sensitive(true);
+        }
+        self.insert_header(name, value)
+    }
+
+    /// Sends a header that carries a secret, treated like `Authorization`
+    pub fn credential_header(self, name: HeaderName, mut value: HeaderValue) -> Self {
+        value.set_sensitive(true);
+        self.insert_header(name, value)
+    }
+
+    fn insert_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sends every header of `headers`, as with [`DownloaderBuilder::header`]
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        for (name, value) in headers {
+            if let Some(name) = name {
+                self = self.header(name, value);
+            }
+        }
+        self
+    }
+
+    /// Sends `Authorization: Bearer <token>`; the value is never logged
+    pub fn bearer_auth(self, token: impl std::fmt::Display) -> Self {
+        self.authorization(format!("Bearer {}", token))
+    }
+
+    /// Sends `Authorization: Basic <credentials>`; the value is never logged
+    pub fn basic_auth(
+        self,
+        username: impl std::fmt::Display,
+        password: impl std::fmt::Display,
+    ) -> Self {
+        let credentials = format!("{}:{}", username, password);
+        self.authorization(format!("Basic {}", STANDARD.encode(credentials)))
+    }
+
+    pub fn user_agent(self, user_agent: HeaderValue) -> Self {
+        self.header(USER_AGENT, user_agent)
+    }
+
+    fn authorization(mut self, value: String) -> Self {
+        match HeaderValue::from_str(&value) {
+            Ok(value) => self.credential_header(AUTHORIZATION, value),
+            // Sending no credentials would fail later and less clearly; the value is not echoed
+            Err(_) => {
+                self.config_error =
+                    Some("credentials contain characters not allowed in a header".to_string());
+                self
+            }
+        }
+    }
+
+    /// Adds `defaults` for headers this download does not set itself
+    pub(crate) fn default_headers(&mut self, defaults: &HeaderMap) {
+        for (name, value) in defaults {
+            if !self.headers.contains_key(name) {
+                let mut value = value.clone();
+                if redirect::is_credential(name) {
+                    value.set_sensitive(true);
+                }
+                self.headers.insert(name.clone(), value);
+            }
+        }
+    }
+
+    /// Pauses, resumes or cancels the download through `handle`, see [`Downloader::handle`]
+    pub fn handle(mut self, handle: DownloadHandle) -> Self {
+        self.control = Some(handle);
+        self
+    }
+
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub(crate) fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+
+    pub(crate) fn has_progress(&self) -> bool {
+        self.progress.is_some()
+    }
+
+    /// Handle of the download, attaching a new one if none was given
+    pub(crate) fn control_handle(&mut self) -> DownloadHandle {
+        self.control.get_or_insert_with(DownloadHandle::new).clone()
+    }
+
+    pub fn build(self) -> Downloader {
+        let derive_title = self.title.is_none() && self.resolve_name;
+        let title = self.title.unwrap_or_else(|| {
+            if self.resolve_name {
+                return redact::redacted(&self.url);
+            }
+            self.output_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| redact::redacted(&self.url))
+        });
+
+        let mut mirrors = vec![self.url.clone()];
+        mirrors.extend(self.mirrors);
+        let sink = self.sink.map(|sink| {
+            let state = SinkState::new(sink, &self.digest_algorithms);
+            Arc::new(tokio::sync::Mutex::new(state))
+        });
+
+        Downloader {
+            url: self.url,
+            mirrors,
+            active_mirror: 0,
+            parallel_mirrors: self.parallel_mirrors,
+            title,
+            derive_title,
+            output_dir: self.resolve_name.then(|| self.output_path.clone()),
+            output_path: self.output_path,
+            sink,
+            progress: self.progress,
+            segments: self.segments,
+            client: self.client.unwrap_or_else(|| {
+                TransportConfig::new()
+                    .build_client()
+                    .expect("default transport settings are valid")
+            }),
+            checksums: self.checksums,
+            digest_algorithms: self.digest_algorithms,
+            digests: Vec::new(),
+            retry_policy: self
+                .retry_policy
+                .unwrap_or_else(|| Arc::new(ExponentialBackoff::default())),
+            redirect_policy: self.redirect_policy,
+            resolution: Default::default(),
+            stall: self.stall,
+            rate_limiters: self.rate_limiters,
+            control: self.control.unwrap_or_default(),
+            headers: self.headers,
+            config_error: self.config_error,
+            durable: self.durable,
+            write_buffer: self.write_buffer,
+            preallocate: self.preallocate,
+            file_tasks: Default::default(),
+            meta_writes: Default::default(),
+            lock: None,
+        }
+    }
+}
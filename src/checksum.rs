@@ -0,0 +1,239 @@
+// checksum.rs
+
+use crate::error::DownloadError;
+use serde::{Deserialize, Serialize};
+use sha1::Digest as _;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+const REHASH_BUFFER_SIZE: usize = 64 * 1024;
+
+// =====================================
+// Algorithms, expected and computed digests
+// =====================================
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Sha256 => "sha256",
+            Self::Sha1 => "sha1",
+            Self::Md5 => "md5",
+            Self::Blake3 => "blake3",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A digest the downloaded file is expected to have
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksum {
+    pub algorithm: ChecksumAlgorithm,
+    /// Lowercase hex encoding
+    pub expected: String,
+}
+
+impl Checksum {
+    pub fn new(algorithm: ChecksumAlgorithm, expected: impl AsRef<str>) -> Self {
+        Self {
+            algorithm,
+            expected: expected.as_ref().trim().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn sha256(expected: impl AsRef<str>) -> Self {
+        Self::new(ChecksumAlgorithm::Sha256, expected)
+    }
+
+    pub fn sha1(expected: impl AsRef<str>) -> Self {
+        Self::new(ChecksumAlgorithm::Sha1, expected)
+    }
+
+    pub fn md5(expected: impl AsRef<str>) -> Self {
+        Self::new(ChecksumAlgorithm::Md5, expected)
+    }
+
+    pub fn blake3(expected: impl AsRef<str>) -> Self {
+        Self::new(ChecksumAlgorithm::Blake3, expected)
+    }
+}
+
+/// A digest computed over the downloaded bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Digest {
+    pub algorithm: ChecksumAlgorithm,
+    /// Lowercase hex encoding
+    pub hex: String,
+}
+
+/// Checks every expected checksum against the computed digests
+pub fn verify(expected: &[Checksum], digests: &[Digest]) -> Result<(), DownloadError> {
+    for checksum in expected {
+        let actual = digests
+            .iter()
+            .find(|digest| digest.algorithm == checksum.algorithm)
+            .map(|digest| digest.hex.as_str())
+            .unwrap_or_default();
+
+        if actual != checksum.expected {
+            return Err(DownloadError::ChecksumMismatch {
+                algorithm: checksum.algorithm,
+                expected: checksum.expected.clone(),
+                actual: actual.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// =====================================
+// Incremental hashing
+// =====================================
+
+enum HasherState {
+    Sha256(sha2::Sha256),
+    Sha1(sha1::Sha1),
+    Md5(md5::Context),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl HasherState {
+    fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+            ChecksumAlgorithm::Sha1 => Self::Sha1(sha1::Sha1::new()),
+            ChecksumAlgorithm::Md5 => Self::Md5(md5::Context::new()),
+            ChecksumAlgorithm::Blake3 => Self::Blake3(Box::default()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Md5(context) => context.consume(data),
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> Digest {
+        let (algorithm, hex) = match self {
+            Self::Sha256(hasher) => (ChecksumAlgorithm::Sha256, to_hex(&hasher.finalize())),
+            Self::Sha1(hasher) => (ChecksumAlgorithm::Sha1, to_hex(&hasher.finalize())),
+            Self::Md5(context) => (ChecksumAlgorithm::Md5, format!("{:x}", context.compute())),
+            Self::Blake3(hasher) => (
+                ChecksumAlgorithm::Blake3,
+                hasher.finalize().to_hex().to_string(),
+            ),
+        };
+        Digest { algorithm, hex }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Feeds the same bytes to one hasher per requested algorithm
+pub(crate) struct MultiHasher {
+    states: Vec<HasherState>,
+}
+
+impl MultiHasher {
+    pub fn new(algorithms: &[ChecksumAlgorithm]) -> Self {
+        let mut unique: Vec<ChecksumAlgorithm> = Vec::new();
+        for algorithm in algorithms {
+            if !unique.contains(algorithm) {
+                unique.push(*algorithm);
+            }
+        }
+
+        Self {
+            states: unique.into_iter().map(HasherState::new).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for state in &mut self.states {
+            state.update(data);
+        }
+    }
+
+    /// Hashes the first `len` bytes already on disk, e.g. the prefix of a resumed part file
+    pub fn update_from_file(&mut self, path: &Path, len: u64) -> io::Result<()> {
+        if self.is_empty() || len == 0 {
+            return Ok(());
+        }
+
+        let mut reader = File::open(path)?.take(len);
+        let mut buf = vec![0u8; REHASH_BUFFER_SIZE];
+        loop {
+            let read = reader.read(&mut buf)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.update(&buf[..read]);
+        }
+    }
+
+    pub fn finalize(self) -> Vec<Digest> {
+        self.states.into_iter().map(HasherState::finalize).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_digests_of_abc() {
+        let mut hasher = MultiHasher::new(&[
+            ChecksumAlgorithm::Sha256,
+            ChecksumAlgorithm::Sha1,
+            ChecksumAlgorithm::Md5,
+            ChecksumAlgorithm::Blake3,
+        ]);
+        hasher.update(b"a");
+        hasher.update(b"bc");
+
+        let digests = hasher.finalize();
+        let expected = [
+            Checksum::sha256("BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD"),
+            Checksum::sha1("a9993e364706816aba3e25717850c26c9cd0d89d"),
+            Checksum::md5("900150983cd24fb0d6963f7d28e17f72"),
+            Checksum::blake3("6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"),
+        ];
+        verify(&expected, &digests).unwrap();
+    }
+
+    #[test]
+    fn verify_reports_mismatch() {
+        let digests = MultiHasher::new(&[ChecksumAlgorithm::Md5]).finalize();
+        let err = verify(&[Checksum::md5("00")], &digests).unwrap_err();
+        assert!(matches!(
+            err,
+            DownloadError::ChecksumMismatch {
+                algorithm: ChecksumAlgorithm::Md5,
+                ..
+            }
+        ));
+    }
+}
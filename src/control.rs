@@ -0,0 +1,97 @@
+// control.rs
+
+use crate::error::DownloadError;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ControlState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+/// Pauses, resumes or cancels a running download from another task.
+///
+/// Clones control the same download. Pausing stops reading while keeping the part file and lock;
+/// cancelling releases the lock and makes `download()` return [`DownloadError::Cancelled`].
+#[derive(Clone, Debug)]
+pub struct DownloadHandle {
+    state: Arc<watch::Sender<ControlState>>,
+}
+
+impl Default for DownloadHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DownloadHandle {
+    pub fn new() -> Self {
+        let (state, _) = watch::channel(ControlState::Running);
+        Self {
+            state: Arc::new(state),
+        }
+    }
+
+    /// Cancels the download; it cannot be resumed afterwards
+    pub fn cancel(&self) {
+        self.state.send_replace(ControlState::Cancelled);
+    }
+
+    pub fn pause(&self) {
+        self.transition(ControlState::Running, ControlState::Paused);
+    }
+
+    pub fn resume(&self) {
+        self.transition(ControlState::Paused, ControlState::Running);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.state.borrow() == ControlState::Paused
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.borrow() == ControlState::Cancelled
+    }
+
+    fn transition(&self, from: ControlState, to: ControlState) {
+        self.state.send_if_modified(|state| {
+            if *state == from {
+                *state = to;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Completes once the download is cancelled
+    pub(crate) async fn cancelled(&self) {
+        let mut rx = self.state.subscribe();
+        // The sender lives in `self`, so the channel cannot close while we wait
+        let _ = rx.wait_for(|state| *state == ControlState::Cancelled).await;
+    }
+
+    /// Waits while paused; returns whether it had to wait
+    pub(crate) async fn checkpoint(&self) -> Result<bool, DownloadError> {
+        let mut rx = self.state.subscribe();
+        let state = *rx.borrow_and_update();
+        match state {
+            ControlState::Running => Ok(false),
+            ControlState::Cancelled => Err(DownloadError::Cancelled),
+            ControlState::Paused => {
+                let state = rx
+                    .wait_for(|state| *state != ControlState::Paused)
+                    .await
+                    .map(|state| *state)
+                    .unwrap_or(ControlState::Cancelled);
+
+                match state {
+                    ControlState::Cancelled => Err(DownloadError::Cancelled),
+                    _ => Ok(true),
+                }
+            }
+        }
+    }
+}
@@ -1,34 +1,47 @@
-use crate::{error::DownloadError, progress::ProgressManager};
+use crate::{
+    builder::DownloaderBuilder,
+    checksum::{self, Checksum, ChecksumAlgorithm, Digest, MultiHasher},
+    control::DownloadHandle,
+    error::DownloadError,
+    filename,
+    meta::PartMeta,
+    mirror_pool::{Assignment, MirrorPool, MirrorSource, PIECES_PER_CONNECTION, RECHECK_INTERVAL},
+    progress::{ProgressEvent, ProgressManager},
+    rate_limit::RateLimiter,
+    redact,
+    redirect::{self, RedirectPolicy},
+    retry::RetryPolicy,
+    segment::{self, SegmentMap},
+    sink::{FileSink, Sink},
+    stall::{StallConfig, StallDetector},
+    validators::Validators,
+    writer::FileTasks,
+};
+use bytes::{Bytes, BytesMut};
 use fs2::FileExt;
-use futures::StreamExt;
-use reqwest::header::{HeaderValue, RANGE};
+use futures::{
+    future::{join_all, try_join_all},
+    Stream,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_DISPOSITION, CONTENT_RANGE, IF_RANGE, RANGE},
+    Url,
+};
 use std::{
     fs::OpenOptions,
-    io::Write,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
+use tokio::sync::Notify;
 
 #[cfg(target_os = "windows")]
 use std::os::windows::ffi::OsStrExt;
 #[cfg(target_os = "windows")]
 use windows_sys::Win32::Storage::FileSystem::{SetFileAttributesW, FILE_ATTRIBUTE_HIDDEN};
 
-const MAX_TITLE_WIDTH: usize = 30;
-const MAX_RETRIES: usize = 5;
 const SPEED_UPDATE_INTERVAL: f64 = 1.0; // seconds
 
-/// Converts bytes to megabytes
-fn bytes_to_mb(bytes: u64) -> f64 {
-    bytes as f64 / (1024.0 * 1024.0)
-}
-
-/// Calculates download speed in MB/s
-fn calculate_speed_mb(bytes: u64, elapsed_seconds: f64) -> f64 {
-    bytes as f64 / elapsed_seconds / (1024.0 * 1024.0)
-}
-
 #[cfg(target_os = "windows")]
 fn set_hidden_attribute(path: &Path) -> std::io::Result<()> {
     let wide_path: Vec<u16> = path
@@ -45,11 +58,123 @@ fn set_hidden_attribute(path: &Path) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Flushes a directory entry change, such as a rename, to disk
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::File::open(dir)?.sync_all()
+}
+
+/// std cannot open a directory for syncing on Windows; the rename is left to the file system journal
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
 fn path_md5_hash(path: &Path) -> String {
     let digest = md5::compute(path.to_string_lossy().as_bytes());
     format!("{:x}", digest)
 }
 
+/// First byte offset of a partial response, from `Content-Range: bytes <start>-<end>/<total>`
+fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+    let value = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let range = value.trim().strip_prefix("bytes ")?;
+    let (start, _) = range.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// How a call to [`Downloader::download`] ended without an error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DownloadOutcome {
+    /// The file was downloaded, verified and moved into place
+    Completed {
+        /// Size of the final file
+        bytes: u64,
+        /// Bytes that were already in the part file when the last attempt started
+        resumed_from: u64,
+    },
+    /// The output file was already complete; nothing was transferred
+    AlreadyComplete,
+    /// Another process holds the download lock; nothing was touched
+    LockedByOther,
+}
+
+/// What a `bytes=0-0` probe tells us about the remote file
+struct RemoteInfo {
+    size: u64,
+    accepts_ranges: bool,
+    validators: Validators,
+}
+
+/// Tracks throughput over `SPEED_UPDATE_INTERVAL` windows
+struct SpeedMeter {
+    last_update: Instant,
+    bytes_since_update: u64,
+    /// Bytes per second over the last closed window
+    speed: Option<f64>,
+}
+
+impl SpeedMeter {
+    fn new() -> Self {
+        Self {
+            last_update: Instant::now(),
+            bytes_since_update: 0,
+            speed: None,
+        }
+    }
+
+    /// Records `bytes` and returns true when a new speed window was closed
+    fn record(&mut self, bytes: u64) -> bool {
+        self.bytes_since_update += bytes;
+
+        let elapsed = self.last_update.elapsed().as_secs_f64();
+        if elapsed < SPEED_UPDATE_INTERVAL {
+            return false;
+        }
+
+        self.speed = Some(self.bytes_since_update as f64 / elapsed);
+        self.last_update = Instant::now();
+        self.bytes_since_update = 0;
+        true
+    }
+}
+
+/// Where redirects from the active URL led
+#[derive(Default)]
+pub(crate) struct Resolution {
+    /// Final URL, requested directly until it fails
+    url: Option<String>,
+    /// A request to `url` failed, so the next attempt resolves it again
+    stale: bool,
+    redirects: Vec<String>,
+}
+
+/// A sink with what is needed to resume it within one download
+pub(crate) struct SinkState {
+    sink: Box<dyn Sink>,
+    /// Validators of the response the sink's bytes came from
+    validators: Option<Validators>,
+    /// Digest state over the bytes in the sink, which cannot be read back
+    hasher: MultiHasher,
+}
+
+impl SinkState {
+    pub fn new(sink: Box<dyn Sink>, digest_algorithms: &[ChecksumAlgorithm]) -> Self {
+        Self {
+            sink,
+            validators: None,
+            hasher: MultiHasher::new(digest_algorithms),
+        }
+    }
+}
+
+/// Shared state of the connections of a segmented download
+struct SegmentState {
+    map: SegmentMap,
+    speed: SpeedMeter,
+}
+
 pub struct ProgressTracker {
     manager: Arc<dyn ProgressManager + Send + Sync>,
     task_id: usize,
@@ -60,54 +185,197 @@ impl ProgressTracker {
         Self { manager, task_id }
     }
 
-    fn update_progress(&self, message: &str) {
-        self.manager.update(self.task_id, message);
+    fn emit(&self, title: &str, event: &ProgressEvent) {
+        self.manager.on_event(self.task_id, title, event);
     }
 }
 
-pub struct Downloader<'a> {
-    url: &'a str,
-    title: &'a str,
-    output_path: PathBuf,
-    progress: Option<ProgressTracker>,
+pub struct Downloader {
+    /// URL of the active mirror
+    pub(crate) url: String,
+    /// Every mirror in order of preference, starting with the primary URL
+    pub(crate) mirrors: Vec<String>,
+    pub(crate) active_mirror: usize,
+    /// Fetch segments from all mirrors at once rather than one at a time
+    pub(crate) parallel_mirrors: bool,
+    pub(crate) title: String,
+    /// Replace the title with the file name once it is resolved
+    pub(crate) derive_title: bool,
+    pub(crate) output_path: PathBuf,
+    /// Directory whose file name is still to be resolved; `output_path` holds it until then
+    pub(crate) output_dir: Option<PathBuf>,
+    /// Destination replacing the output file; shared so a cancelled attempt cannot drop it
+    pub(crate) sink: Option<Arc<tokio::sync::Mutex<SinkState>>>,
+    pub(crate) progress: Option<ProgressTracker>,
+    pub(crate) segments: usize,
+    pub(crate) client: reqwest::Client,
+    pub(crate) checksums: Vec<Checksum>,
+    pub(crate) digest_algorithms: Vec<ChecksumAlgorithm>,
+    pub(crate) digests: Vec<Digest>,
+    pub(crate) retry_policy: Arc<dyn RetryPolicy>,
+    pub(crate) redirect_policy: RedirectPolicy,
+    pub(crate) resolution: Mutex<Resolution>,
+    pub(crate) stall: StallConfig,
+    pub(crate) rate_limiters: Vec<Arc<RateLimiter>>,
+    pub(crate) control: DownloadHandle,
+    pub(crate) headers: HeaderMap,
+    /// A setting that could not be applied, reported instead of downloading
+    pub(crate) config_error: Option<String>,
+    pub(crate) durable: bool,
+    pub(crate) write_buffer: usize,
+    pub(crate) preallocate: bool,
+    /// Blocking writes to the part file, which outlive an attempt that is dropped
+    pub(crate) file_tasks: FileTasks,
+    /// Serializes metadata saves from blocking tasks, which share a temporary file
+    pub(crate) meta_writes: Arc<Mutex<()>>,
+    /// Lock file held by the current attempt
+    pub(crate) lock: Option<std::fs::File>,
 }
 
-impl<'a> Downloader<'a> {
+impl Downloader {
     pub fn new(
-        url: &'a str,
-        title: &'a str,
-        output_path: &'a str,
+        url: impl Into<String>,
+        title: impl Into<String>,
+        output_path: impl AsRef<Path>,
         progress: Option<ProgressTracker>,
     ) -> Self {
-        Self {
-            url,
-            title,
-            output_path: PathBuf::from(output_path),
-            progress,
-        }
-    }
-
-    /// Truncates title to fit within display width
-    fn truncated_title(&self) -> String {
-        if self.title.chars().count() > MAX_TITLE_WIDTH {
-            let mut truncated = self
-                .title
-                .chars()
-                .take(MAX_TITLE_WIDTH - 1)
-                .collect::<String>();
-            truncated.push('…');
-            truncated
-        } else {
-            self.title.to_string()
+        let mut builder = DownloaderBuilder::new(url, output_path).title(title);
+        if let Some(progress) = progress {
+            builder = builder.progress(progress);
+        }
+        builder.build()
+    }
+
+    pub fn builder(url: impl Into<String>, output_path: impl AsRef<Path>) -> DownloaderBuilder {
+        DownloaderBuilder::new(url, output_path)
+    }
+
+    /// Like [`Downloader::builder`], but the file name inside `dir` comes from the response
+    pub fn builder_in_dir(url: impl Into<String>, dir: impl AsRef<Path>) -> DownloaderBuilder {
+        DownloaderBuilder::in_dir(url, dir)
+    }
+
+    /// Like [`Downloader::builder`], but the bytes go to `sink` instead of a file
+    pub fn builder_to_sink(url: impl Into<String>, sink: impl Sink + 'static) -> DownloaderBuilder {
+        DownloaderBuilder::to_sink(url, sink)
+    }
+
+    /// Path of the final file; in directory mode, the directory until the name is resolved, and
+    /// empty when downloading into a sink
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+
+    /// Digests computed over the last verified file, one per configured algorithm
+    pub fn digests(&self) -> &[Digest] {
+        &self.digests
+    }
+
+    /// URL the active mirror redirected to, which requests go to until it fails
+    pub fn resolved_url(&self) -> Option<String> {
+        self.resolution.lock().unwrap().url.clone()
+    }
+
+    /// Redirects followed on the way to [`Downloader::resolved_url`], in order
+    pub fn redirects(&self) -> Vec<String> {
+        self.resolution.lock().unwrap().redirects.clone()
+    }
+
+    /// Handle that pauses, resumes or cancels this download from another task
+    pub fn handle(&self) -> DownloadHandle {
+        self.control.clone()
+    }
+
+    /// Waits while the download is paused, or fails once it is cancelled
+    async fn checkpoint(&self) -> Result<bool, DownloadError> {
+        if self.control.is_paused() {
+            self.emit(ProgressEvent::Paused);
+        }
+        let waited = self.control.checkpoint().await?;
+        if waited {
+            self.emit(ProgressEvent::Resumed);
+        }
+        Ok(waited)
+    }
+
+    /// Next chunk of a response body, read once the download is not paused and let through by
+    /// the rate limiters, or `None` at its end
+    async fn next_chunk<S>(
+        &self,
+        stream: &mut S,
+        stall: &mut StallDetector,
+    ) -> Result<Option<Bytes>, DownloadError>
+    where
+        S: Stream<Item = reqwest::Result<Bytes>> + Unpin,
+    {
+        if self.checkpoint().await? {
+            stall.reset();
+        }
+        let Some(chunk) = stall.next(stream).await? else {
+            return Ok(None);
+        };
+        // Holding back on purpose is not the connection stalling
+        stall.exclude(self.throttle(chunk.len()).await);
+        Ok(Some(chunk))
+    }
+
+    /// Waits until every configured limiter allows `bytes` more to be read; returns the time
+    /// spent waiting
+    async fn throttle(&self, bytes: usize) -> Duration {
+        let started = Instant::now();
+        for limiter in &self.rate_limiters {
+            limiter.acquire(bytes as u64).await;
+        }
+        started.elapsed()
+    }
+
+    fn emit(&self, event: ProgressEvent) {
+        if let Some(ref progress) = self.progress {
+            if self.parallel_mirrors && self.mirrors.len() > 1 {
+                let label = format!("{} via {} mirrors", self.title, self.mirrors.len());
+                progress.emit(&label, &event);
+            } else if self.mirrors.len() > 1 {
+                let host = reqwest::Url::parse(&self.url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                    .unwrap_or_default();
+                progress.emit(&format!("{} via {}", self.title, host), &event);
+            } else {
+                progress.emit(&self.title, &event);
+            }
         }
     }
 
+    /// The part file belongs to the download, not to whichever mirror served it
+    fn primary_url(&self) -> &str {
+        &self.mirrors[0]
+    }
+
     fn temp_path(&self) -> PathBuf {
         let mut path = self.output_path.clone();
         path.set_extension("part");
         path
     }
 
+    fn quarantine_path(&self) -> PathBuf {
+        let mut path = self.output_path.clone();
+        path.set_extension("corrupt");
+        path
+    }
+
+    fn meta_path(&self) -> PathBuf {
+        let mut path = self.output_path.clone();
+        path.set_extension("part.meta");
+        path
+    }
+
+    fn output_dir(&self) -> &Path {
+        self.output_path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    }
+
     fn lock_path(&self) -> PathBuf {
         let hash = path_md5_hash(&self.output_path);
         let lock_name = if cfg!(windows) {
@@ -119,12 +387,131 @@ impl<'a> Downloader<'a> {
         self.output_path.with_file_name(lock_name)
     }
 
-    async fn probe_remote_size(&self, client: &reqwest::Client) -> Result<u64, DownloadError> {
-        let response = client
-            .get(self.url)
-            .header("Range", "bytes=0-0")
-            .send()
+    /// Where requests for `url` go: the resolved URL for the active mirror, once one is known
+    fn target_url(&self, url: &str) -> String {
+        match self.resolution.lock().unwrap().url {
+            Some(ref resolved) if url == self.url => resolved.clone(),
+            _ => url.to_string(),
+        }
+    }
+
+    /// GET request for the file at `url` carrying the configured headers
+    fn request(&self, client: &reqwest::Client, url: &str) -> reqwest::RequestBuilder {
+        let target = self.target_url(url);
+        let mut headers = self.headers.clone();
+        if let (Ok(url), Ok(resolved)) = (Url::parse(url), Url::parse(&target)) {
+            if !redirect::same_origin(&url, &resolved) {
+                redirect::remove_credentials(&mut headers);
+            }
+        }
+        client.get(target).headers(headers)
+    }
+
+    /// Sends `request`, following redirects as the policy allows.
+    ///
+    /// Redirects from the active URL are remembered, so later requests go straight to where they
+    /// led. A resolved URL whose request fails is marked to be resolved again.
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, DownloadError> {
+        let (client, request) = request.build_split();
+        let request = request?;
+        let origin = request.url().clone();
+        let active = Url::parse(&self.url).ok();
+        let resolved = Url::parse(&self.target_url(&self.url)).ok();
+
+        let result = self.follow_redirects(&client, request).await;
+
+        let mut resolution = self.resolution.lock().unwrap();
+        // Only the resolved URL failing itself, such as an expired signed one, makes it stale
+        let to_resolved = resolution.url.is_some() && resolved.as_ref() == Some(&origin);
+        let failed = match result {
+            // A 416 only says the part file is already complete
+            Ok((ref response, _)) => {
+                let status = response.status();
+                status.is_server_error()
+                    || (status.is_client_error()
+                        && status != reqwest::StatusCode::RANGE_NOT_SATISFIABLE)
+            }
+            Err(_) => true,
+        };
+        if to_resolved && failed {
+            resolution.stale = true;
+        }
+
+        let (response, hops) = result?;
+        if resolved.as_ref() == Some(&origin) {
+            if active.as_ref() == Some(&origin) {
+                resolution.redirects.clear();
+            }
+            if !hops.is_empty() {
+                resolution.redirects.extend(hops);
+                resolution.url = Some(response.url().to_string());
+            }
+        }
+        Ok(response)
+    }
+
+    /// Executes `request` and each redirect the policy allows, returning the last response and
+    /// the URLs it went through
+    async fn follow_redirects(
+        &self,
+        client: &reqwest::Client,
+        request: reqwest::Request,
+    ) -> Result<(reqwest::Response, Vec<String>), DownloadError> {
+        let origin = request.url().clone();
+        let mut url = origin.clone();
+        let mut headers = request.headers().clone();
+
+        let mut hops = Vec::new();
+        let mut response = client.execute(request).await?;
+        while let Some(next) = self
+            .redirect_policy
+            .next_hop(&origin, &response, hops.len())?
+        {
+            if !redirect::same_origin(&url, &next) {
+                redirect::remove_credentials(&mut headers);
+            }
+            hops.push(next.to_string());
+            url = next.clone();
+
+            let mut request = reqwest::Request::new(reqwest::Method::GET, next);
+            *request.headers_mut() = headers.clone();
+            response = client.execute(request).await?;
+        }
+
+        // The policy never saw the redirects a client followed on its own
+        if hops.is_empty() && *response.url() != origin {
+            return Err(DownloadError::Redirect(format!(
+                "the client followed a redirect to {} itself; build it with `TransportConfig` or \
+                 `redirect::Policy::none()` so the redirect policy applies",
+                redact::redacted(response.url().as_str())
+            )));
+        }
+        Ok((response, hops))
+    }
+
+    async fn probe_remote_size(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<u64, DownloadError> {
+        self.probe_remote(client, url)
+            .await
+            .map(|remote| remote.size)
+    }
+
+    async fn probe_remote(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<RemoteInfo, DownloadError> {
+        let response = self
+            .send(self.request(client, url).header(RANGE, "bytes=0-0"))
             .await?;
+        let accepts_ranges = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        let validators = Validators::from_response(&response, 0);
 
         // Try to extract size from Content-Range header first
         if let Some(content_range) = response.headers().get("Content-Range") {
@@ -136,9 +523,15 @@ impl<'a> Downloader<'a> {
                 .nth(1)
                 .ok_or(DownloadError::UnsupportedServer)?;
 
-            return total_size_str
+            let size = total_size_str
                 .parse()
-                .map_err(|_| DownloadError::UnsupportedServer);
+                .map_err(|_| DownloadError::UnsupportedServer)?;
+            self.emit(ProgressEvent::Probed { total: Some(size) });
+            return Ok(RemoteInfo {
+                size,
+                accepts_ranges,
+                validators,
+            });
         }
 
         // Fall back to Content-Length
@@ -146,16 +539,22 @@ impl<'a> Downloader<'a> {
             let size_str = content_length
                 .to_str()
                 .map_err(|_| DownloadError::UnsupportedServer)?;
-            return size_str
+            let size = size_str
                 .parse()
-                .map_err(|_| DownloadError::UnsupportedServer);
+                .map_err(|_| DownloadError::UnsupportedServer)?;
+            self.emit(ProgressEvent::Probed { total: Some(size) });
+            return Ok(RemoteInfo {
+                size,
+                accepts_ranges: false,
+                validators,
+            });
         }
 
         Err(DownloadError::UnsupportedServer)
     }
 
     /// Check if the file already exists and is complete
-    async fn should_skip_download(&self) -> Result<bool, DownloadError> {
+    async fn should_skip_download(&mut self) -> Result<bool, DownloadError> {
         let final_path = &self.output_path;
 
         // Only check if final file exists and there's no temp file
@@ -170,8 +569,7 @@ impl<'a> Downloader<'a> {
         }
 
         // Check if file size matches remote size
-        let client = reqwest::Client::new();
-        let remote_size = match self.probe_remote_size(&client).await {
+        let remote_size = match self.probe_remote_size(&self.client, &self.url).await {
             Ok(size) => size,
             Err(DownloadError::UnsupportedServer) => {
                 // If we can't determine remote size, we can't verify completeness
@@ -190,24 +588,73 @@ impl<'a> Downloader<'a> {
         };
 
         if local_size == remote_size {
-            if let Some(ref progress) = self.progress {
-                progress.update_progress(&format!(
-                    "File already complete: {} — skipping download",
-                    self.truncated_title()
-                ));
+            let mut hasher = MultiHasher::new(&self.digest_algorithms);
+            hasher.update_from_file(final_path, local_size)?;
+            self.digests = hasher.finalize();
+
+            if checksum::verify(&self.checksums, &self.digests).is_err() {
+                // Complete by size but not by content: set it aside and start over
+                std::fs::rename(final_path, self.quarantine_path())?;
+                return Ok(false);
             }
+
+            self.emit(ProgressEvent::Skipped);
             return Ok(true);
         }
 
-        // File exists but is incomplete - rename to temp for resumption
-        std::fs::rename(final_path, temp_path)?;
+        // Nothing records what an incomplete final file holds, so it is downloaded again and
+        // only replaced once the new copy is complete
         Ok(false)
     }
 
+    /// Fails with [`DownloadError::DiskFull`] unless `needed` bytes fit next to the output file
+    fn check_free_space(&self, needed: u64) -> Result<(), DownloadError> {
+        let dir = self.output_dir();
+        let available = fs2::available_space(dir)?;
+
+        if available < needed {
+            return Err(DownloadError::DiskFull(std::io::Error::new(
+                std::io::ErrorKind::StorageFull,
+                format!(
+                    "{} bytes needed in {}, {} available",
+                    needed,
+                    dir.display(),
+                    available
+                ),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Takes the download lock, failing with [`DownloadError::LockHeld`] when another process
+    /// holds it
+    fn acquire_lock(&mut self) -> Result<(), DownloadError> {
+        let lock_file = self.create_lock_file()?;
+        if lock_file.try_lock_exclusive().is_err() {
+            self.emit(ProgressEvent::LockHeld);
+            return Err(DownloadError::LockHeld {
+                path: self.lock_path(),
+            });
+        }
+
+        self.lock = Some(lock_file);
+        Ok(())
+    }
+
+    /// Removes the lock file while still holding it, then unlocks
+    fn release_lock(&mut self) -> Result<(), DownloadError> {
+        if let Some(lock_file) = self.lock.take() {
+            std::fs::remove_file(self.lock_path())?;
+            drop(lock_file);
+        }
+        Ok(())
+    }
+
     fn create_lock_file(&self) -> Result<std::fs::File, std::io::Error> {
         let lock_path = self.lock_path();
         let lock_file = OpenOptions::new()
             .create(true)
+            .truncate(false)
             .write(true)
             .open(&lock_path)?;
 
@@ -221,165 +668,1071 @@ impl<'a> Downloader<'a> {
         &self,
         response: reqwest::Response,
         existing_len: u64,
-        mut file: std::fs::File,
-    ) -> Result<(), DownloadError> {
+        sink: &mut dyn Sink,
+        hasher: &mut MultiHasher,
+    ) -> Result<u64, DownloadError> {
         let total_size = response.content_length().map(|size| size + existing_len);
 
         let mut stream = response.bytes_stream();
         let mut downloaded = existing_len;
+        let mut speed = SpeedMeter::new();
+        let mut stall = StallDetector::new(self.stall);
 
-        let mut last_update = Instant::now();
-        let mut bytes_since_update = 0u64;
-        let mut speed_message = String::new();
-
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
-            file.write_all(&chunk)?;
-            downloaded += chunk.len() as u64;
-            bytes_since_update += chunk.len() as u64;
-
-            let elapsed = last_update.elapsed().as_secs_f64();
-            if elapsed >= SPEED_UPDATE_INTERVAL {
-                let speed_mb = calculate_speed_mb(bytes_since_update, elapsed);
-                speed_message = format!(" | {:.2} MB/s", speed_mb);
-
-                last_update = Instant::now();
-                bytes_since_update = 0;
-            }
-
-            if let Some(ref progress) = self.progress {
-                let downloaded_mb = bytes_to_mb(downloaded);
-                let truncated_title = self.truncated_title();
-
-                if let Some(total) = total_size {
-                    let total_mb = bytes_to_mb(total);
-                    let percentage = (downloaded as f64 / total as f64) * 100.0;
-
-                    progress.update_progress(&format!(
-                        "Downloading {}: {:.2} MB / {:.2} MB ({:.2}%){}",
-                        truncated_title, downloaded_mb, total_mb, percentage, speed_message
-                    ));
-                } else {
-                    progress.update_progress(&format!(
-                        "Downloaded {}: {:.2} MB{}",
-                        truncated_title, downloaded_mb, speed_message
-                    ));
-                }
+        let result = async {
+            loop {
+                let Some(chunk) = self.next_chunk(&mut stream, &mut stall).await? else {
+                    return Ok(());
+                };
+                // Only bytes the sink took are hashed and counted, as a retry resumes after those;
+                // a failed write may still have taken part of the chunk
+                let before = sink.written();
+                let result = sink.write(chunk.clone()).await;
+                let taken = chunk.slice(..(sink.written() - before) as usize);
+                hasher.update(&taken);
+                downloaded += taken.len() as u64;
+                speed.record(taken.len() as u64);
+                result?;
+
+                self.emit(ProgressEvent::Bytes {
+                    downloaded,
+                    total: total_size,
+                    speed: speed.speed,
+                });
             }
         }
+        .await;
 
-        Ok(())
+        // The sink must hold every byte before it is retried or finalized; a failed write to the
+        // part file closes its queue, so the writer's own error takes precedence
+        sink.finish().await?;
+        result.map(|_| downloaded)
     }
 
-    async fn try_download(&mut self) -> Result<(), DownloadError> {
-        // First, check if we should skip downloading entirely
-        if self.should_skip_download().await? {
-            return Ok(());
+    /// Loads the metadata of an existing part file; called with the lock held.
+    ///
+    /// A part file whose metadata is missing, unreadable or was recorded for another URL is
+    /// discarded, as nothing vouches for its bytes.
+    fn load_part_meta(&self) -> Result<Option<PartMeta>, DownloadError> {
+        let meta_path = self.meta_path();
+        if !self.temp_path().exists() {
+            if meta_path.exists() {
+                std::fs::remove_file(&meta_path)?;
+            }
+            return Ok(None);
         }
 
+        match PartMeta::load(&meta_path) {
+            Ok(meta) if meta.url == self.primary_url() => Ok(Some(meta)),
+            _ => {
+                self.discard_part()?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn part_meta(&self, validators: &Validators, segments: Option<&SegmentMap>) -> PartMeta {
+        let mut meta = PartMeta::new(self.primary_url(), validators.clone(), &self.checksums);
+        meta.segments = segments.cloned();
+        // A URL another mirror redirected to means nothing for the primary
+        if self.active_mirror == 0 {
+            meta.resolved_url = self.resolved_url();
+        }
+        meta
+    }
+
+    fn save_part_meta(
+        &self,
+        validators: &Validators,
+        segments: Option<&SegmentMap>,
+    ) -> std::io::Result<()> {
+        let _writing = self.meta_writes.lock().unwrap();
+        self.part_meta(validators, segments).save(&self.meta_path())
+    }
+
+    /// Builds the segment map to resume from, or `None` to use a single stream
+    async fn prepare_segment_map(
+        &self,
+        client: &reqwest::Client,
+        meta: Option<PartMeta>,
+    ) -> Result<Option<(SegmentMap, Validators)>, DownloadError> {
         let temp_path = self.temp_path();
-        let existing_len = temp_path.metadata().map(|meta| meta.len()).unwrap_or(0);
+        let segmented = meta.as_ref().is_some_and(|meta| meta.segments.is_some());
 
-        // Prepare request with range if resuming
-        let client = reqwest::Client::new();
-        let mut request = client.get(self.url);
+        let remote = match self.probe_remote(client, &self.url).await {
+            Ok(remote) => remote,
+            Err(DownloadError::UnsupportedServer) => {
+                return self.abandon_segments(segmented).map(|_| None)
+            }
+            Err(e) => return Err(e),
+        };
 
-        if existing_len > 0 {
-            let range_value = HeaderValue::from_str(&format!("bytes={}-", existing_len))
-                .map_err(|_| DownloadError::InvalidRange)?;
-            request = request.header(RANGE, range_value);
+        if !remote.accepts_ranges || remote.size == 0 {
+            return self.abandon_segments(segmented).map(|_| None);
         }
 
-        let response = request.send().await?;
+        // Only a part file recorded by a single stream holds exactly the bytes it is long; the
+        // length of one without metadata may be a preallocation that was never recorded
+        let mut existing_len = 0;
+        if let Some(meta) = meta {
+            let unchanged = meta.validators.is_compatible(&remote.validators);
+            match meta.segments {
+                Some(map) if map.total == remote.size && unchanged => {
+                    return Ok(Some((map, remote.validators)))
+                }
+                // Stale map or changed remote: the part file cannot be trusted either
+                Some(_) => self.discard_part()?,
+                None if !unchanged => self.discard_part()?,
+                // A part file left by a single stream becomes the prefix of the first segments
+                None => existing_len = temp_path.metadata().map(|meta| meta.len()).unwrap_or(0),
+            }
+        }
 
-        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
-            return Err(DownloadError::RangeNotSatisfiable);
+        // Small pieces let each mirror's share follow its throughput
+        let pieces = if self.parallel_mirrors && self.mirrors.len() > 1 {
+            self.segments.max(self.mirrors.len()) * PIECES_PER_CONNECTION
+        } else {
+            self.segments
+        };
+        let mut map = SegmentMap::plan(remote.size, pieces);
+        for segment in &mut map.segments {
+            segment.downloaded = existing_len
+                .saturating_sub(segment.start)
+                .min(segment.len());
         }
 
-        let response = response.error_for_status()?;
+        Ok(Some((map, remote.validators)))
+    }
 
-        // Create and lock lock file
-        let lock_file = self.create_lock_file()?;
-        if lock_file.try_lock_exclusive().is_err() {
-            if let Some(ref progress) = self.progress {
-                progress.update_progress("Another instance is downloading — aborting");
-            }
-            return Ok(());
+    /// Drops segmented resume state that can no longer be used
+    fn abandon_segments(&self, segmented: bool) -> Result<(), DownloadError> {
+        if segmented {
+            self.discard_part()?;
         }
+        Ok(())
+    }
 
-        // Open temp file for appending
+    /// Removes the part file together with everything recorded about it
+    fn discard_part(&self) -> Result<(), DownloadError> {
+        let temp_path = self.temp_path();
+        if temp_path.exists() {
+            std::fs::remove_file(&temp_path)?;
+        }
+        self.remove_sidecars()
+    }
+
+    /// Removes the resume state kept next to the part file
+    fn remove_sidecars(&self) -> Result<(), DownloadError> {
+        let meta_path = self.meta_path();
+        if meta_path.exists() {
+            std::fs::remove_file(meta_path)?;
+        }
+        Ok(())
+    }
+
+    async fn download_segments(
+        &mut self,
+        client: &reqwest::Client,
+        map: SegmentMap,
+        validators: Validators,
+    ) -> Result<DownloadOutcome, DownloadError> {
+        let temp_path = self.temp_path();
+
+        // A sparse or preallocated part file only holds the downloaded bytes so far
+        self.check_free_space(map.total - map.downloaded())?;
+
+        // Open temp file for positioned writes
         let file = OpenOptions::new()
             .create(true)
-            .append(true)
-            .open(&temp_path)?;
+            .truncate(false)
+            .write(true)
+            .open(&temp_path)
+            .map(Arc::new)?;
+        // The map is recorded first, so a full-size part file never lacks one
+        self.save_part_meta(&validators, Some(&map))?;
+        if self.preallocate {
+            file.allocate(map.total)?;
+        } else if file.metadata()?.len() < map.total {
+            file.set_len(map.total)?;
+        }
+        let resumed_from = map.downloaded();
 
-        // Download chunks
-        self.download_chunks(response, existing_len, file).await?;
+        let pending: Vec<_> = (0..map.segments.len())
+            .filter(|&index| !map.segments[index].is_complete())
+            .collect();
+        let state = Mutex::new(SegmentState {
+            map,
+            speed: SpeedMeter::new(),
+        });
 
-        // Atomic finalize
-        std::fs::rename(&temp_path, &self.output_path)?;
-        std::fs::remove_file(self.lock_path())?;
+        let result = if self.parallel_mirrors && self.mirrors.len() > 1 {
+            self.fetch_from_mirrors(client, &file, &state, &validators)
+                .await
+        } else {
+            let source = MirrorSource {
+                url: self.url.clone(),
+                validators: validators.clone(),
+            };
+            try_join_all(pending.into_iter().map(|index| {
+                self.fetch_segment(client, &file, &state, index, &source, &validators)
+            }))
+            .await
+            .map(|_| ())
+        };
 
-        Ok(())
+        // Writes dropped with a revoked or failed segment must not land after the final map
+        self.file_tasks.idle().await;
+        let state = state.into_inner().unwrap();
+        if let Err(e @ DownloadError::RemoteChanged) = result {
+            drop(file);
+            self.discard_part()?;
+            return Err(e);
+        }
+        self.save_part_meta(&validators, Some(&state.map))?;
+        result?;
+
+        if !state.map.is_complete() {
+            return Err(DownloadError::InvalidResponse(
+                "Segmented download ended before all ranges were fetched".to_string(),
+            ));
+        }
+
+        // Segments arrive out of order, so the digest is computed over the finished file
+        drop(file);
+        let mut hasher = MultiHasher::new(&self.digest_algorithms);
+        hasher.update_from_file(&temp_path, state.map.total)?;
+
+        self.finalize(hasher, Some(state.map.total))?;
+
+        Ok(DownloadOutcome::Completed {
+            bytes: state.map.total,
+            resumed_from,
+        })
     }
 
-    pub async fn download(&mut self) -> Result<(), DownloadError> {
-        for attempt in 0..MAX_RETRIES {
-            match self.try_download().await {
-                Ok(()) => return Ok(()),
-                Err(DownloadError::RangeNotSatisfiable) => {
-                    // Try to finalize if temp file exists
-                    let temp_path = self.temp_path();
-                    if temp_path.exists() {
-                        let _ = std::fs::rename(&temp_path, &self.output_path);
-                    }
-                    return Ok(());
-                }
-                Err(DownloadError::UnsupportedServer) => return Ok(()),
-                Err(e) if attempt == MAX_RETRIES - 1 => return Err(e),
-                Err(_) => {
-                    let delay = Duration::from_secs(2_u64.pow(attempt as u32));
-                    tokio::time::sleep(delay).await;
-                    continue;
+    /// Fetches segments from every mirror that reports the same length as the active one.
+    ///
+    /// Connections are spread over the mirrors and take segments as they free up, so faster
+    /// mirrors serve more of the file. A mirror that fails hands its segments to the others.
+    async fn fetch_from_mirrors(
+        &self,
+        client: &reqwest::Client,
+        file: &Arc<std::fs::File>,
+        state: &Mutex<SegmentState>,
+        validators: &Validators,
+    ) -> Result<(), DownloadError> {
+        let total = state.lock().unwrap().map.total;
+
+        let mut sources = vec![MirrorSource {
+            url: self.url.clone(),
+            validators: validators.clone(),
+        }];
+        for url in self.mirrors.iter().filter(|url| **url != self.url) {
+            // A mirror without ranges or with another length cannot serve pieces of this file
+            match self.probe_remote(client, url).await {
+                Ok(remote) if remote.accepts_ranges && remote.size == total => {
+                    sources.push(MirrorSource {
+                        url: url.clone(),
+                        validators: remote.validators,
+                    })
                 }
+                _ => {}
             }
         }
 
-        unreachable!("Loop should always return or break before reaching end");
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::progress::StdoutProgressManager;
-    use futures::future::join_all;
+        let pool = Mutex::new(MirrorPool::new(sources.len()));
+        let changed = Notify::new();
+        let connections = self.segments.max(sources.len());
+        join_all((0..connections).map(|connection| {
+            let mirror = connection % sources.len();
+            self.mirror_connection(
+                client,
+                file,
+                state,
+                &pool,
+                &changed,
+                mirror,
+                &sources[mirror],
+                validators,
+            )
+        }))
+        .await;
 
-    struct TestDownload<'a> {
-        url: &'a str,
-        title: &'a str,
-        output_path: &'a str,
+        let error = pool.into_inner().unwrap().into_error();
+        match error {
+            Some(e) if !state.lock().unwrap().map.is_complete() => Err(e),
+            _ => Ok(()),
+        }
     }
 
-    impl<'a> TestDownload<'a> {
-        const fn new(url: &'a str, title: &'a str, output_path: &'a str) -> Self {
-            Self {
-                url,
-                title,
-                output_path,
+    /// One connection to `source`, fetching segments until none are left or the mirror fails
+    #[allow(clippy::too_many_arguments)]
+    async fn mirror_connection(
+        &self,
+        client: &reqwest::Client,
+        file: &Arc<std::fs::File>,
+        state: &Mutex<SegmentState>,
+        pool: &Mutex<MirrorPool>,
+        changed: &Notify,
+        mirror: usize,
+        source: &MirrorSource,
+        validators: &Validators,
+    ) {
+        loop {
+            // Registered before looking, so a release in between is not missed
+            let notified = changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let assignment = {
+                let state = state.lock().unwrap();
+                pool.lock().unwrap().assign(&state.map, mirror)
+            };
+            match assignment {
+                Assignment::Done => return,
+                Assignment::Wait => {
+                    tokio::select! {
+                        _ = notified => {}
+                        _ = tokio::time::sleep(RECHECK_INTERVAL) => {}
+                    }
+                }
+                Assignment::Fetch { index, revoke } => {
+                    let fetch = self.fetch_segment(client, file, state, index, source, validators);
+                    let result = tokio::select! {
+                        result = fetch => result,
+                        // Taken over by a faster mirror; the bytes written so far are kept
+                        _ = revoke.notified() => Ok(()),
+                    };
+                    // The part file follows the validators of the active URL, the first source;
+                    // another mirror serving a different file is only dropped from the pool
+                    let result = match result {
+                        Err(DownloadError::RemoteChanged) if mirror != 0 => {
+                            Err(DownloadError::InvalidResponse(format!(
+                                "Mirror {} no longer serves the file being downloaded",
+                                redact::redacted(&source.url)
+                            )))
+                        }
+                        result => result,
+                    };
+                    {
+                        let state = state.lock().unwrap();
+                        pool.lock().unwrap().release(&state.map, index, result);
+                    }
+                    changed.notify_waiters();
+                }
             }
         }
     }
 
-    const TEST_DOWNLOADS: [TestDownload; 3] = [
-        TestDownload::new(
-            "https://ash-speed.hetzner.com/100MB.bin",
-            "100MB.bin",
-            "100MB.bin",
-        ),
-        TestDownload::new(
-            "https://ash-speed.hetzner.com/100MB.bin",
-            "100MB.bin",
+    async fn fetch_segment(
+        &self,
+        client: &reqwest::Client,
+        file: &Arc<std::fs::File>,
+        state: &Mutex<SegmentState>,
+        index: usize,
+        source: &MirrorSource,
+        validators: &Validators,
+    ) -> Result<(), DownloadError> {
+        let segment = state.lock().unwrap().map.segments[index];
+
+        let range_value =
+            HeaderValue::from_str(&format!("bytes={}-{}", segment.next_offset(), segment.end))
+                .map_err(|_| DownloadError::InvalidRange)?;
+        let mut request = self.request(client, &source.url).header(RANGE, range_value);
+        if let Some(if_range) = source.validators.if_range() {
+            request = request.header(IF_RANGE, if_range);
+        }
+        let response = self.send(request).await?.error_for_status()?;
+
+        // With If-Range, a full response means the remote file is no longer the one we split
+        if response.status() == reqwest::StatusCode::OK && source.validators.if_range().is_some() {
+            return Err(DownloadError::RemoteChanged);
+        }
+        if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Err(DownloadError::InvalidResponse(format!(
+                "Expected 206 for segment {}, got {}",
+                index,
+                response.status()
+            )));
+        }
+        if content_range_start(&response) != Some(segment.next_offset()) {
+            return Err(DownloadError::InvalidResponse(format!(
+                "Segment {} expected to start at byte {}, got Content-Range {:?}",
+                index,
+                segment.next_offset(),
+                response.headers().get(CONTENT_RANGE)
+            )));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut offset = segment.next_offset();
+        let mut stall = StallDetector::new(self.stall);
+        // Received bytes wait here until `write_buffer` of them can go to disk in one write
+        let mut pending = BytesMut::new();
+
+        let result: Result<(), DownloadError> = async {
+            loop {
+                // A pause may outlast the process, so nothing is left in the buffer meanwhile
+                if self.control.is_paused() {
+                    self.write_segment(file, state, index, validators, &mut pending)
+                        .await?;
+                }
+                let Some(chunk) = self.next_chunk(&mut stream, &mut stall).await? else {
+                    return Ok(());
+                };
+                let remaining = (segment.end + 1).saturating_sub(offset) as usize;
+                pending.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+                offset += chunk.len().min(remaining) as u64;
+
+                if offset > segment.end {
+                    return Ok(());
+                }
+                if pending.len() >= self.write_buffer {
+                    self.write_segment(file, state, index, validators, &mut pending)
+                        .await?;
+                }
+            }
+        }
+        .await;
+
+        // What arrived before the connection failed is kept, so a retry continues after it
+        self.write_segment(file, state, index, validators, &mut pending)
+            .await?;
+        result?;
+
+        if offset <= segment.end {
+            return Err(DownloadError::InvalidResponse(format!(
+                "Segment {} closed at byte {} of {}",
+                index, offset, segment.end
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Writes the bytes buffered for segment `index` after those it already holds, and records
+    /// them in the map
+    async fn write_segment(
+        &self,
+        file: &Arc<std::fs::File>,
+        state: &Mutex<SegmentState>,
+        index: usize,
+        validators: &Validators,
+        pending: &mut BytesMut,
+    ) -> Result<(), DownloadError> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let chunk = pending.split().freeze();
+        let len = chunk.len() as u64;
+        let offset = state.lock().unwrap().map.segments[index].next_offset();
+        let target = file.clone();
+        self.file_tasks
+            .run(move || segment::write_all_at(&target, &chunk, offset))
+            .await?;
+
+        let checkpoint = {
+            let mut state = state.lock().unwrap();
+            state.map.segments[index].downloaded += len;
+            let closed_window = state.speed.record(len);
+            self.emit(ProgressEvent::Bytes {
+                downloaded: state.map.downloaded(),
+                total: Some(state.map.total),
+                speed: state.speed.speed,
+            });
+            closed_window.then(|| self.part_meta(validators, Some(&state.map)))
+        };
+        if let Some(meta) = checkpoint {
+            let path = self.meta_path();
+            let writes = self.meta_writes.clone();
+            self.file_tasks
+                .run(move || {
+                    let _writing = writes.lock().unwrap();
+                    meta.save(&path)
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Verifies the part file against the expected size and checksums and moves it into place.
+    ///
+    /// A short part file is kept so a retry can resume it; one that is too long or fails checksum
+    /// verification cannot be resumed and is discarded or quarantined.
+    fn finalize(
+        &mut self,
+        hasher: MultiHasher,
+        expected_len: Option<u64>,
+    ) -> Result<(), DownloadError> {
+        let temp_path = self.temp_path();
+
+        if let Some(expected) = expected_len {
+            let actual = temp_path.metadata()?.len();
+            if actual > expected {
+                self.discard_part()?;
+            }
+            if actual != expected {
+                return Err(DownloadError::SizeMismatch { expected, actual });
+            }
+        }
+
+        self.digests = hasher.finalize();
+
+        if let Err(e) = checksum::verify(&self.checksums, &self.digests) {
+            std::fs::rename(&temp_path, self.quarantine_path())?;
+            self.remove_sidecars()?;
+            return Err(e);
+        }
+
+        if self.durable {
+            // The data must reach the disk before the rename makes the file look complete
+            OpenOptions::new()
+                .write(true)
+                .open(&temp_path)?
+                .sync_all()?;
+        }
+
+        // Atomic finalize
+        std::fs::rename(&temp_path, &self.output_path)?;
+        if self.durable {
+            sync_dir(self.output_dir())?;
+        }
+        self.remove_sidecars()?;
+        self.emit(ProgressEvent::Finished);
+
+        Ok(())
+    }
+
+    /// Names the output file inside `dir` after the response to a one-byte request
+    async fn resolve_output_path(&mut self, dir: &Path) -> Result<(), DownloadError> {
+        let response = self
+            .send(
+                self.request(&self.client, &self.url)
+                    .header(RANGE, "bytes=0-0"),
+            )
+            .await?
+            .error_for_status()?;
+        let disposition = response
+            .headers()
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok());
+        // Neither an existing file nor another download's part file may be taken over
+        let name = filename::avoid_sidecars(filename::resolve(disposition, response.url()));
+        let name = filename::first_free(&name, |candidate| {
+            let path = dir.join(candidate);
+            path.exists()
+                || match PartMeta::load(&path.with_extension("part.meta")) {
+                    Ok(meta) => meta.url != self.primary_url(),
+                    // Sidecars no metadata vouches for would be discarded as stale
+                    Err(_) => ["part", "corrupt", "part.tmp"]
+                        .iter()
+                        .any(|extension| path.with_extension(extension).exists()),
+                }
+        });
+
+        if self.derive_title {
+            self.title = name.clone();
+        }
+        self.output_path = dir.join(name);
+        self.output_dir = None;
+        Ok(())
+    }
+
+    /// One attempt, started once the download is not paused
+    async fn attempt(&mut self) -> Result<DownloadOutcome, DownloadError> {
+        self.checkpoint().await?;
+        if let Some(state) = self.sink.clone() {
+            return self.download_to_sink(&mut *state.lock().await).await;
+        }
+        if let Some(dir) = self.output_dir.clone() {
+            self.resolve_output_path(&dir).await?;
+        }
+
+        // Everything that reads, writes or cleans up the part file happens under the lock
+        match self.acquire_lock() {
+            Ok(()) => {}
+            // Not a failure: the file is simply being downloaded elsewhere
+            Err(DownloadError::LockHeld { .. }) => return Ok(DownloadOutcome::LockedByOther),
+            Err(e) => return Err(e),
+        }
+        let result = match self.try_download().await {
+            // Nothing lies past the part file, so it should hold the whole file already
+            Err(DownloadError::RangeNotSatisfiable) if self.is_single_stream_part() => {
+                self.finalize_existing_part().await
+            }
+            result => result,
+        };
+        // Segments that failed alongside another may have left writes running
+        self.file_tasks.idle().await;
+        let released = self.release_lock();
+        let outcome = result?;
+        released.map(|_| outcome)
+    }
+
+    /// Streams the file into the sink, continuing after the bytes it already holds
+    async fn download_to_sink(
+        &mut self,
+        state: &mut SinkState,
+    ) -> Result<DownloadOutcome, DownloadError> {
+        let written = state.sink.written();
+        let mut request = self.request(&self.client, &self.url);
+        if written > 0 {
+            let range_value = HeaderValue::from_str(&format!("bytes={}-", written))
+                .map_err(|_| DownloadError::InvalidRange)?;
+            request = request.header(RANGE, range_value);
+            if let Some(if_range) = state.validators.as_ref().and_then(Validators::if_range) {
+                request = request.header(IF_RANGE, if_range);
+            }
+        }
+
+        let response = self.send(request).await?;
+        if written > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            return self.finish_full_sink(state).await;
+        }
+        let response = response.error_for_status()?;
+        let validators = Validators::from_response(&response, written);
+
+        let mut resumed_from = written;
+        if written > 0 && response.status() == reqwest::StatusCode::OK {
+            // A sink that cannot discard its bytes fails here rather than receive them twice
+            state.sink.restart().await?;
+            state.hasher = MultiHasher::new(&self.digest_algorithms);
+            resumed_from = 0;
+        } else if written > 0 {
+            if content_range_start(&response) != Some(written) {
+                return Err(DownloadError::InvalidResponse(format!(
+                    "Expected a partial response starting at byte {}, got Content-Range {:?}",
+                    written,
+                    response.headers().get(CONTENT_RANGE)
+                )));
+            }
+            if let Some(ref saved) = state.validators {
+                if !saved.is_compatible(&validators) {
+                    state.sink.restart().await?;
+                    state.hasher = MultiHasher::new(&self.digest_algorithms);
+                    state.validators = None;
+                    return Err(DownloadError::RemoteChanged);
+                }
+            }
+        }
+        state.validators = Some(validators.clone());
+
+        self.emit(ProgressEvent::Probed {
+            total: response.content_length().map(|size| size + resumed_from),
+        });
+
+        let bytes = self
+            .download_chunks(response, resumed_from, &mut *state.sink, &mut state.hasher)
+            .await?;
+        if let Some(expected) = self.expected_size(&validators).await? {
+            if bytes != expected {
+                return Err(DownloadError::SizeMismatch {
+                    expected,
+                    actual: bytes,
+                });
+            }
+        }
+
+        self.finish_sink(state, bytes, resumed_from)
+    }
+
+    /// Finishes a sink that already holds the whole file, as a 416 suggests, after an attempt
+    /// that failed once every byte was written
+    async fn finish_full_sink(
+        &mut self,
+        state: &mut SinkState,
+    ) -> Result<DownloadOutcome, DownloadError> {
+        let written = state.sink.written();
+        let remote = self.probe_remote(&self.client, &self.url).await?;
+        if let Some(ref saved) = state.validators {
+            if !saved.is_compatible(&remote.validators) {
+                state.sink.restart().await?;
+                state.hasher = MultiHasher::new(&self.digest_algorithms);
+                state.validators = None;
+                return Err(DownloadError::RemoteChanged);
+            }
+        }
+        if remote.size != written {
+            return Err(DownloadError::SizeMismatch {
+                expected: remote.size,
+                actual: written,
+            });
+        }
+
+        state.sink.finish().await?;
+        self.finish_sink(state, written, written)
+    }
+
+    /// Verifies the digests over the sink's bytes
+    fn finish_sink(
+        &mut self,
+        state: &mut SinkState,
+        bytes: u64,
+        resumed_from: u64,
+    ) -> Result<DownloadOutcome, DownloadError> {
+        let hasher =
+            std::mem::replace(&mut state.hasher, MultiHasher::new(&self.digest_algorithms));
+        self.digests = hasher.finalize();
+        checksum::verify(&self.checksums, &self.digests)?;
+        self.emit(ProgressEvent::Finished);
+
+        Ok(DownloadOutcome::Completed {
+            bytes,
+            resumed_from,
+        })
+    }
+
+    async fn try_download(&mut self) -> Result<DownloadOutcome, DownloadError> {
+        // First, check if we should skip downloading entirely
+        if self.should_skip_download().await? {
+            return Ok(DownloadOutcome::AlreadyComplete);
+        }
+
+        let client = self.client.clone();
+
+        let mut meta = self.load_part_meta()?;
+
+        // A segment map left by an earlier run is resumed even if segmenting is now off
+        // Preallocating needs the segment map to track progress, as the part file is full-size
+        if self.segments > 1
+            || self.preallocate
+            || (self.parallel_mirrors && self.mirrors.len() > 1)
+            || meta.as_ref().is_some_and(|meta| meta.segments.is_some())
+        {
+            if let Some((map, validators)) = self.prepare_segment_map(&client, meta.take()).await? {
+                return self.download_segments(&client, map, validators).await;
+            }
+            // The part file may have been discarded while preparing
+            meta = self.load_part_meta()?;
+        }
+
+        let temp_path = self.temp_path();
+        let mut existing_len = temp_path.metadata().map(|meta| meta.len()).unwrap_or(0);
+        let saved = meta
+            .filter(|_| existing_len > 0)
+            .map(|meta| meta.validators);
+
+        // Prepare request with range if resuming
+        let mut request = self.request(&client, &self.url);
+
+        if existing_len > 0 {
+            let range_value = HeaderValue::from_str(&format!("bytes={}-", existing_len))
+                .map_err(|_| DownloadError::InvalidRange)?;
+            request = request.header(RANGE, range_value);
+
+            // Only resume if the remote file is still the one the part file came from
+            if let Some(if_range) = saved.as_ref().and_then(Validators::if_range) {
+                request = request.header(IF_RANGE, if_range);
+            }
+        }
+
+        let response = self.send(request).await?;
+
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            return Err(DownloadError::RangeNotSatisfiable);
+        }
+
+        let response = response.error_for_status()?;
+        let validators = Validators::from_response(&response, existing_len);
+
+        // A full response to a range request means the server ignored the range or the part file
+        // is stale: start over rather than append the whole body after it
+        let restart = existing_len > 0 && response.status() == reqwest::StatusCode::OK;
+        if response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+            && content_range_start(&response) != Some(existing_len)
+        {
+            return Err(DownloadError::InvalidResponse(format!(
+                "Expected a partial response starting at byte {}, got Content-Range {:?}",
+                existing_len,
+                response.headers().get(CONTENT_RANGE)
+            )));
+        }
+        if restart {
+            existing_len = 0;
+        } else if let Some(ref saved) = saved {
+            if !saved.is_compatible(&validators) {
+                self.discard_part()?;
+                return Err(DownloadError::RemoteChanged);
+            }
+        }
+
+        self.emit(ProgressEvent::Probed {
+            total: response.content_length().map(|size| size + existing_len),
+        });
+
+        if let Some(total) = validators.total {
+            self.check_free_space(total.saturating_sub(existing_len))?;
+        }
+
+        // Open temp file for appending
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&temp_path)?;
+        if restart {
+            file.set_len(0)?;
+        }
+        if existing_len == 0 || saved.is_none() {
+            self.save_part_meta(&validators, None)?;
+        }
+
+        // Resumed bytes are rehashed so the digest covers the whole file
+        let mut hasher = MultiHasher::new(&self.digest_algorithms);
+        hasher.update_from_file(&temp_path, existing_len)?;
+
+        // Download chunks
+        let mut sink = FileSink::new(
+            file,
+            existing_len,
+            self.write_buffer,
+            self.file_tasks.clone(),
+        )?;
+        let bytes = self
+            .download_chunks(response, existing_len, &mut sink, &mut hasher)
+            .await?;
+
+        let expected = self.expected_size(&validators).await?;
+        self.finalize(hasher, expected)?;
+
+        Ok(DownloadOutcome::Completed {
+            bytes,
+            resumed_from: existing_len,
+        })
+    }
+
+    /// Whether a single stream recorded the part file, so that its length is what it holds; a
+    /// segmented or preallocated one is full-size from the start
+    fn is_single_stream_part(&self) -> bool {
+        self.temp_path().exists()
+            && PartMeta::load(&self.meta_path()).is_ok_and(|meta| meta.segments.is_none())
+    }
+
+    /// Size the downloaded file must have, from the response or else from a separate probe
+    async fn expected_size(&self, validators: &Validators) -> Result<Option<u64>, DownloadError> {
+        if validators.total.is_some() {
+            return Ok(validators.total);
+        }
+        // A chunked response does not say how long it should have been
+        match self.probe_remote_size(&self.client, &self.url).await {
+            Ok(size) => Ok(Some(size)),
+            Err(DownloadError::UnsupportedServer) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Finalizes a part file the server reports nothing beyond, once its size is confirmed
+    async fn finalize_existing_part(&mut self) -> Result<DownloadOutcome, DownloadError> {
+        let expected = self.probe_remote_size(&self.client, &self.url).await?;
+
+        let temp_path = self.temp_path();
+        let len = temp_path.metadata()?.len();
+        let mut hasher = MultiHasher::new(&self.digest_algorithms);
+        hasher.update_from_file(&temp_path, len)?;
+        self.finalize(hasher, Some(expected))?;
+
+        Ok(DownloadOutcome::Completed {
+            bytes: len,
+            resumed_from: len,
+        })
+    }
+
+    /// Downloads the file, retrying through the retry policy.
+    ///
+    /// Errors are wrapped in [`DownloadError::Context`] with the URL, output path and attempt.
+    pub async fn download(&mut self) -> Result<DownloadOutcome, DownloadError> {
+        if let Some(ref problem) = self.config_error {
+            return Err(DownloadError::InvalidConfig(problem.clone()).with_context(
+                &self.url,
+                &self.output_path,
+                0,
+            ));
+        }
+        self.restore_resolved_url();
+
+        let mut attempt = 0;
+        self.run_attempts(&mut attempt)
+            .await
+            .map_err(|e| e.with_context(&self.url, &self.output_path, attempt))
+    }
+
+    /// Goes straight to the URL a previous run was redirected to, if its part file is still there
+    fn restore_resolved_url(&mut self) {
+        if self.output_dir.is_some()
+            || self.sink.is_some()
+            || self.resolution.get_mut().unwrap().url.is_some()
+        {
+            return;
+        }
+        let resolved = PartMeta::load(&self.meta_path())
+            .ok()
+            .filter(|meta| meta.url == self.primary_url() && self.temp_path().exists())
+            .and_then(|meta| meta.resolved_url);
+        self.resolution.get_mut().unwrap().url = resolved;
+    }
+
+    /// Whether another mirror, or a fresh redirect, could succeed where the active URL failed
+    fn should_fail_over(&self, error: &DownloadError) -> bool {
+        self.retry_policy.is_retryable(error)
+            || matches!(
+                error.root(),
+                DownloadError::HttpStatus {
+                    code: 403 | 404 | 410,
+                    ..
+                }
+            )
+    }
+
+    /// Switches to the next mirror that agrees with the part file.
+    ///
+    /// Returns false once every mirror was tried, going back to the primary for the next round.
+    async fn fail_over(&mut self) -> Result<bool, DownloadError> {
+        // Only bytes recorded with validators can be checked against another server
+        let saved = PartMeta::load(&self.meta_path())
+            .ok()
+            .filter(|meta| meta.url == self.primary_url() && self.temp_path().exists())
+            .map(|meta| meta.validators);
+
+        for index in self.active_mirror + 1..self.mirrors.len() {
+            self.url = self.mirrors[index].clone();
+            self.active_mirror = index;
+
+            let agrees = match (&saved, self.probe_remote(&self.client, &self.url).await) {
+                (_, Err(_)) => false,
+                (None, Ok(_)) => true,
+                (Some(saved), Ok(remote)) => {
+                    saved.total.is_none_or(|total| total == remote.size)
+                        && saved.is_compatible(&remote.validators)
+                }
+            };
+            if agrees {
+                self.emit(ProgressEvent::MirrorChanged {
+                    url: redact::redacted(&self.url),
+                });
+                return Ok(true);
+            }
+        }
+
+        self.url = self.mirrors[0].clone();
+        self.active_mirror = 0;
+        Ok(false)
+    }
+
+    async fn run_attempts(
+        &mut self,
+        attempt: &mut usize,
+    ) -> Result<DownloadOutcome, DownloadError> {
+        self.emit(ProgressEvent::Started);
+
+        let control = self.control.clone();
+        let mut failed_over = false;
+        loop {
+            // Trying each mirror in turn counts as one attempt
+            if !failed_over {
+                *attempt += 1;
+            }
+
+            // Cancelling drops the attempt at its next await point
+            let result = tokio::select! {
+                result = self.attempt() => result,
+                _ = control.cancelled() => Err(DownloadError::Cancelled),
+            };
+            // A cancelled attempt never got to release its lock, and the writes it dropped must
+            // end before another process may take the part file
+            if let Err(DownloadError::Cancelled) = result {
+                self.file_tasks.idle().await;
+                self.release_lock()?;
+                self.emit(ProgressEvent::Cancelled);
+                return result;
+            }
+
+            // A stale resolved URL is resolved again from the active one on the next attempt,
+            // which may succeed where the stale URL failed for good
+            let resolution = self.resolution.get_mut().unwrap();
+            let stale = std::mem::take(&mut resolution.stale);
+            match result {
+                Ok(outcome) => return Ok(outcome),
+                Err(e) => {
+                    if stale {
+                        resolution.url = None;
+                    }
+
+                    failed_over = !stale
+                        && self.mirrors.len() > 1
+                        && self.should_fail_over(&e)
+                        && tokio::select! {
+                            switched = self.fail_over() => switched?,
+                            _ = control.cancelled() => true,
+                        };
+                    if failed_over {
+                        continue;
+                    }
+
+                    let retryable = if stale {
+                        self.should_fail_over(&e)
+                    } else {
+                        self.retry_policy.is_retryable(&e)
+                    };
+                    if *attempt >= self.retry_policy.max_attempts() || !retryable {
+                        self.emit(ProgressEvent::Failed {
+                            error: e.to_string(),
+                        });
+                        return Err(e);
+                    }
+
+                    let delay = self.retry_policy.delay(*attempt);
+                    self.emit(ProgressEvent::Retrying {
+                        attempt: *attempt,
+                        delay,
+                        error: e.to_string(),
+                    });
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = control.cancelled() => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::DownloadManager;
+    use crate::progress::StdoutProgressManager;
+    use crate::retry::ExponentialBackoff;
+    use crate::sink::{MemorySink, WriterSink};
+    use crate::test_server::{payload, temp_output, ServerOptions, TestServer};
+    use std::{
+        pin::Pin,
+        sync::atomic::Ordering,
+        task::{Context, Poll},
+    };
+
+    struct TestDownload<'a> {
+        url: &'a str,
+        title: &'a str,
+        output_path: &'a str,
+    }
+
+    impl<'a> TestDownload<'a> {
+        const fn new(url: &'a str, title: &'a str, output_path: &'a str) -> Self {
+            Self {
+                url,
+                title,
+                output_path,
+            }
+        }
+    }
+
+    const TEST_DOWNLOADS: [TestDownload; 3] = [
+        TestDownload::new(
+            "https://ash-speed.hetzner.com/100MB.bin",
+            "100MB.bin",
+            "100MB.bin",
+        ),
+        TestDownload::new(
+            "https://ash-speed.hetzner.com/100MB.bin",
+            "100MB.bin",
             "100MB.bin",
         ),
         TestDownload::new(
@@ -392,30 +1745,1084 @@ mod tests {
     #[tokio::test]
     async fn test_concurrent_downloads() {
         let progress = Arc::new(StdoutProgressManager::new());
-        let tasks: Vec<_> = TEST_DOWNLOADS
+        let client = crate::TransportConfig::new().build_client().unwrap();
+        let manager = DownloadManager::new(2).with_progress(progress);
+
+        let handles: Vec<_> = TEST_DOWNLOADS
             .iter()
             .map(|test| {
-                let progress_clone = progress.clone();
-                let url = test.url;
-                let title = test.title;
-                let output_path = test.output_path;
-
-                tokio::spawn(async move {
-                    let task_id = progress_clone.register();
-                    let mut downloader = Downloader::new(
-                        url,
-                        title,
-                        output_path,
-                        Some(ProgressTracker::new(progress_clone, task_id)),
-                    );
-                    downloader.download().await
-                })
+                manager.submit(
+                    Downloader::builder(test.url, test.output_path)
+                        .title(test.title)
+                        .client(client.clone()),
+                )
             })
             .collect();
 
-        let results = join_all(tasks).await;
+        let results = join_all(handles).await;
         for result in results {
-            assert!(result.unwrap().is_ok());
+            assert!(result.is_ok());
         }
     }
+
+    /// Leaves `bytes` behind as the part file of an interrupted single-stream download
+    fn leave_part(downloader: &Downloader, bytes: &[u8], total: usize) {
+        std::fs::write(downloader.temp_path(), bytes).unwrap();
+        let validators = Validators {
+            total: Some(total as u64),
+            ..Validators::default()
+        };
+        downloader.save_part_meta(&validators, None).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_segmented_download_resumes_partial_file() {
+        let body = payload(256 * 1024 + 7);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("segmented.bin");
+
+        // Leave a single-stream part file behind, as an interrupted run would
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .segments(4)
+            .build();
+        let partial = downloader.temp_path();
+        leave_part(&downloader, &body[..1000], body.len());
+
+        let outcome = downloader.download().await.unwrap();
+
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Completed {
+                bytes: body.len() as u64,
+                resumed_from: 1000,
+            }
+        );
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert!(!partial.exists());
+        assert!(!downloader.meta_path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_segments_ignore_part_file_without_metadata() {
+        let body = payload(64 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("segmented_unrecorded.bin");
+
+        // A full-size part file whose map was never saved holds nothing known to be downloaded
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .segments(4)
+            .build();
+        std::fs::write(downloader.temp_path(), vec![0u8; body.len()]).unwrap();
+
+        let outcome = downloader.download().await.unwrap();
+
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Completed {
+                resumed_from: 0,
+                ..
+            }
+        ));
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[derive(Default)]
+    struct RecordingProgress {
+        events: Mutex<Vec<ProgressEvent>>,
+    }
+
+    impl ProgressManager for RecordingProgress {
+        fn register(&self) -> usize {
+            0
+        }
+
+        fn update(&self, _line: usize, _content: &str) {}
+
+        fn on_event(&self, _line: usize, _title: &str, event: &ProgressEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_segment_writes_are_coalesced() {
+        let body = payload(512 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("coalesced.bin");
+        let recorder = Arc::new(RecordingProgress::default());
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .segments(2)
+            .write_buffer(128 * 1024)
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .build();
+        downloader.download().await.unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+
+        // Each write is recorded as it lands: a full buffer, or the end of a segment
+        let events = recorder.events.lock().unwrap();
+        let writes = events
+            .iter()
+            .filter(|event| matches!(event, ProgressEvent::Bytes { .. }))
+            .count();
+        assert_eq!(writes, 4);
+    }
+
+    #[tokio::test]
+    async fn test_progress_events_are_typed() {
+        let body = payload(64 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("events.bin");
+        let recorder = Arc::new(RecordingProgress::default());
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .build();
+        downloader.download().await.unwrap();
+
+        let events = recorder.events.lock().unwrap();
+        assert_eq!(events.first(), Some(&ProgressEvent::Started));
+        assert_eq!(events.last(), Some(&ProgressEvent::Finished));
+        assert!(events.contains(&ProgressEvent::Probed {
+            total: Some(body.len() as u64)
+        }));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ProgressEvent::Bytes { downloaded, .. } if *downloaded == body.len() as u64
+        )));
+    }
+
+    fn sha256_of(data: &[u8]) -> String {
+        let mut hasher = MultiHasher::new(&[ChecksumAlgorithm::Sha256]);
+        hasher.update(data);
+        hasher.finalize().remove(0).hex
+    }
+
+    #[tokio::test]
+    async fn test_checksum_covers_resumed_prefix() {
+        let body = payload(100 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("checksum_ok.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .checksum(Checksum::sha256(sha256_of(&body)))
+            .digest(ChecksumAlgorithm::Md5)
+            .build();
+        leave_part(&downloader, &body[..4096], body.len());
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert_eq!(downloader.digests().len(), 2);
+        assert_eq!(
+            downloader.digests()[1].hex,
+            format!("{:x}", md5::compute(&body))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_quarantines_file() {
+        let body = payload(10 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("checksum_bad.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .checksum(Checksum::sha256(sha256_of(b"something else")))
+            .build();
+        let err = downloader.download().await.unwrap_err();
+
+        assert!(matches!(err.root(), DownloadError::ChecksumMismatch { .. }));
+        assert!(!PathBuf::from(&output_path).exists());
+        assert!(downloader.quarantine_path().exists());
+        assert_eq!(downloader.digests()[0].hex, sha256_of(&body));
+    }
+
+    #[tokio::test]
+    async fn test_resume_restarts_when_etag_changed() {
+        let body = payload(32 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone()).etag("\"v2\"")).await;
+        let output_path = temp_output("etag_changed.bin");
+
+        // Part file and validators left behind by a download of an older version
+        let mut downloader = Downloader::builder(&server.url, &output_path).build();
+        std::fs::write(downloader.temp_path(), vec![0u8; 5000]).unwrap();
+        let validators = Validators {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            total: Some(body.len() as u64),
+        };
+        downloader.save_part_meta(&validators, None).unwrap();
+
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert!(!downloader.meta_path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_part_file_from_other_url_is_discarded() {
+        let body = payload(16 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("other_url.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path).build();
+        std::fs::write(downloader.temp_path(), vec![0u8; 3000]).unwrap();
+        PartMeta::new(
+            "http://elsewhere.invalid/file.bin",
+            Validators::default(),
+            &[],
+        )
+        .save(&downloader.meta_path())
+        .unwrap();
+
+        // Only the holder of the lock may decide the part file is not its own
+        let mut holder = Downloader::builder(&server.url, &output_path).build();
+        holder.acquire_lock().unwrap();
+        assert_eq!(
+            downloader.download().await.unwrap(),
+            DownloadOutcome::LockedByOther
+        );
+        assert!(downloader.temp_path().exists());
+        holder.release_lock().unwrap();
+
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_part_file_without_metadata_is_not_resumed() {
+        let body = payload(16 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("unrecorded.bin");
+
+        // Full-size, as a preallocation whose map was lost would be, so the resume gets a 416
+        let mut downloader = Downloader::builder(&server.url, &output_path).build();
+        std::fs::write(downloader.temp_path(), vec![0u8; body.len()]).unwrap();
+
+        let outcome = downloader.download().await.unwrap();
+
+        assert!(matches!(
+            outcome,
+            DownloadOutcome::Completed {
+                resumed_from: 0,
+                ..
+            }
+        ));
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors_fail_fast() {
+        let server = TestServer::start(ServerOptions::new(payload(16))).await;
+        let output_path = temp_output("missing.bin");
+
+        let started = Instant::now();
+        let mut downloader =
+            Downloader::builder(format!("{}.missing", server.url), &output_path).build();
+        let err = downloader.download().await.unwrap_err();
+
+        assert!(
+            matches!(err.root(), DownloadError::HttpStatus { code: 404, url } if url.ends_with(".missing"))
+        );
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_resumes_stalled_transfer() {
+        let body = payload(64 * 1024);
+        let server =
+            TestServer::start(ServerOptions::new(body.clone()).stall_after(10_000, 1)).await;
+        let output_path = temp_output("stalled.bin");
+        let recorder = Arc::new(RecordingProgress::default());
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .idle_timeout(std::time::Duration::from_millis(200))
+            .retry_policy(
+                ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        let events = recorder.events.lock().unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ProgressEvent::Retrying { error, .. } if error.starts_with("Transfer stalled")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_slows_download() {
+        let body = payload(300 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("rate_limited.bin");
+
+        // 200 KB of burst, then 100 KB at 200 KB/s
+        let started = Instant::now();
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .rate_limit(200 * 1024)
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_is_not_a_stall() {
+        let body = payload(300 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("rate_limited_not_stalled.bin");
+
+        // Far below the minimum throughput overall, but only because of the limiter
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .rate_limit(200 * 1024)
+            .min_throughput(1024 * 1024, std::time::Duration::from_millis(100))
+            .idle_timeout(std::time::Duration::from_millis(100))
+            .retry_policy(ExponentialBackoff::new().with_max_attempts(1))
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_keeps_part_and_releases_lock() {
+        let body = payload(1024 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("cancelled.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .rate_limit(256 * 1024)
+            .build();
+        let handle = downloader.handle();
+        let task = tokio::spawn(async move {
+            let result = downloader.download().await;
+            (downloader, result)
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        handle.cancel();
+
+        let (downloader, result) = task.await.unwrap();
+        assert!(matches!(
+            result.unwrap_err().root(),
+            DownloadError::Cancelled
+        ));
+        assert!(!downloader.lock_path().exists());
+        let part_len = downloader.temp_path().metadata().unwrap().len();
+        assert!(part_len > 0 && part_len < body.len() as u64);
+
+        // A paused download holds its place and finishes from the part file once resumed
+        let recorder = Arc::new(RecordingProgress::default());
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .build();
+        let handle = downloader.handle();
+        handle.pause();
+        let resume = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            handle.resume();
+        });
+        downloader.download().await.unwrap();
+        resume.await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        let events = recorder.events.lock().unwrap();
+        assert!(events.contains(&ProgressEvent::Paused));
+        assert!(events.contains(&ProgressEvent::Resumed));
+    }
+
+    #[tokio::test]
+    async fn test_full_response_to_range_request_restarts() {
+        let body = payload(20 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone()).ignore_range()).await;
+        let output_path = temp_output("ignored_range.bin");
+
+        // Part file recorded without an ETag, so the resume carries no If-Range
+        let mut downloader = Downloader::builder(&server.url, &output_path).build();
+        leave_part(&downloader, &body[..4000], body.len());
+
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_misaligned_partial_response_is_rejected() {
+        let body = payload(20 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone()).range_skew(100)).await;
+        let output_path = temp_output("misaligned_range.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .retry_policy(ExponentialBackoff::new().with_max_attempts(1))
+            .build();
+        leave_part(&downloader, &body[..4000], body.len());
+
+        let result = downloader.download().await;
+
+        let err = result.unwrap_err();
+        assert!(matches!(err.root(), DownloadError::InvalidResponse(_)));
+        assert_eq!(err.url(), Some(server.url.as_str()));
+        assert_eq!(err.output_path(), Some(Path::new(&output_path)));
+        assert_eq!(
+            std::fs::read(downloader.temp_path()).unwrap(),
+            &body[..4000]
+        );
+        assert!(!Path::new(&output_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_outcome_distinguishes_skip_and_lock() {
+        let body = payload(8 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("outcomes.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path).build();
+        downloader.acquire_lock().unwrap();
+        let mut other = Downloader::builder(&server.url, &output_path).build();
+        assert!(matches!(
+            other.acquire_lock(),
+            Err(DownloadError::LockHeld { path }) if path == other.lock_path()
+        ));
+        assert_eq!(
+            other.download().await.unwrap(),
+            DownloadOutcome::LockedByOther
+        );
+        downloader.release_lock().unwrap();
+
+        assert!(matches!(
+            downloader.download().await.unwrap(),
+            DownloadOutcome::Completed {
+                resumed_from: 0,
+                ..
+            }
+        ));
+        assert_eq!(
+            downloader.download().await.unwrap(),
+            DownloadOutcome::AlreadyComplete
+        );
+    }
+
+    #[tokio::test]
+    async fn test_short_body_is_resumed_not_finalized() {
+        let body = payload(40 * 1024);
+        let server =
+            TestServer::start(ServerOptions::new(body.clone()).truncate_after(5000, 1)).await;
+        let output_path = temp_output("short_body.bin");
+        let recorder = Arc::new(RecordingProgress::default());
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .retry_policy(
+                ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .build();
+        leave_part(&downloader, &body[..1000], body.len());
+
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        let events = recorder.events.lock().unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ProgressEvent::Retrying { error, .. } if error.starts_with("Size mismatch")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_short_chunked_body_is_resumed_not_finalized() {
+        let body = payload(40 * 1024);
+        let server = TestServer::start(
+            ServerOptions::new(body.clone())
+                .chunked()
+                .truncate_after(5000, 1),
+        )
+        .await;
+        let output_path = temp_output("short_chunked_body.bin");
+        let recorder = Arc::new(RecordingProgress::default());
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .retry_policy(
+                ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        let events = recorder.events.lock().unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ProgressEvent::Retrying { error, .. } if error.starts_with("Size mismatch")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_part_is_not_finalized() {
+        let body = payload(10 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("oversized_part.bin");
+
+        // The server answers 416 to a resume past its end
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .retry_policy(
+                ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .build();
+        let mut oversized = body.clone();
+        oversized.extend_from_slice(&[0u8; 100]);
+        leave_part(&downloader, &oversized, body.len());
+
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_durable_finalize() {
+        let body = payload(16 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("durable.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .durable(true)
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert!(!downloader.temp_path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_preallocated_download() {
+        let body = payload(48 * 1024 + 3);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let output_path = temp_output("preallocated.bin");
+
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .preallocate(true)
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_preallocated_part_is_allocated_and_resumed() {
+        let body = payload(256 * 1024);
+        // The probe and the first range both hang
+        let server = TestServer::start(ServerOptions::new(body.clone()).stall_after(1000, 2)).await;
+        let output_path = temp_output("preallocated_interrupted.bin");
+
+        let builder = || {
+            Downloader::builder(&server.url, &output_path)
+                .preallocate(true)
+                .idle_timeout(std::time::Duration::from_millis(200))
+                .retry_policy(ExponentialBackoff::new().with_max_attempts(1))
+        };
+        let mut downloader = builder().build();
+        let err = downloader.download().await.unwrap_err();
+        assert!(matches!(err.root(), DownloadError::Stalled(_)));
+
+        let part = downloader.temp_path().metadata().unwrap();
+        assert_eq!(part.len(), body.len() as u64);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            assert!(part.blocks() * 512 >= body.len() as u64);
+        }
+        let map = PartMeta::load(&downloader.meta_path())
+            .unwrap()
+            .segments
+            .unwrap();
+        assert_eq!(map.downloaded(), 1000);
+
+        let outcome = builder().build().download().await.unwrap();
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Completed {
+                bytes: body.len() as u64,
+                resumed_from: 1000,
+            }
+        );
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_disk_full_fails_before_part_file_exists() {
+        let body = payload(4 * 1024);
+        let server =
+            TestServer::start(ServerOptions::new(body.clone()).claim_total(u64::MAX / 2)).await;
+        let output_path = temp_output("disk_full.bin");
+
+        let started = Instant::now();
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .preallocate(true)
+            .build();
+        let err = downloader.download().await.unwrap_err();
+
+        assert!(matches!(err.root(), DownloadError::DiskFull(_)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert!(!downloader.temp_path().exists());
+        assert!(!downloader.meta_path().exists());
+        assert!(!downloader.lock_path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_directory_mode_names_file_from_response() {
+        let body = payload(4 * 1024);
+        let dir = PathBuf::from(temp_output("named"));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let server = TestServer::start(
+            ServerOptions::new(body.clone())
+                .content_disposition("attachment; filename=\"../escape.bin\""),
+        )
+        .await;
+        let mut downloader = Downloader::builder_in_dir(&server.url, &dir).build();
+        downloader.download().await.unwrap();
+        assert_eq!(downloader.output_path(), dir.join("escape.bin"));
+        assert_eq!(std::fs::read(dir.join("escape.bin")).unwrap(), body);
+
+        // Without Content-Disposition the URL names the file
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let mut downloader = Downloader::builder_in_dir(&server.url, &dir).build();
+        downloader.download().await.unwrap();
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), body);
+
+        // An existing file is left alone rather than resumed into or replaced
+        std::fs::write(dir.join("file.bin"), b"unrelated").unwrap();
+        let mut downloader = Downloader::builder_in_dir(&server.url, &dir).build();
+        downloader.download().await.unwrap();
+        assert_eq!(downloader.output_path(), dir.join("file (1).bin"));
+        assert_eq!(std::fs::read(dir.join("file.bin")).unwrap(), b"unrelated");
+
+        // A name that passes for a sidecar would be its own part file
+        let server = TestServer::start(
+            ServerOptions::new(body.clone()).content_disposition("attachment; filename=x.part"),
+        )
+        .await;
+        let mut downloader = Downloader::builder_in_dir(&server.url, &dir).build();
+        downloader.download().await.unwrap();
+        assert_eq!(downloader.output_path(), dir.join("x.part.download"));
+        assert_eq!(std::fs::read(dir.join("x.part.download")).unwrap(), body);
+
+        // Nor is a stray part file that nothing records, which would be discarded
+        std::fs::write(dir.join("report.part"), b"unrelated").unwrap();
+        let server = TestServer::start(
+            ServerOptions::new(body.clone()).content_disposition("attachment; filename=report.pdf"),
+        )
+        .await;
+        let mut downloader = Downloader::builder_in_dir(&server.url, &dir).build();
+        downloader.download().await.unwrap();
+        assert_eq!(downloader.output_path(), dir.join("report (1).pdf"));
+        assert_eq!(
+            std::fs::read(dir.join("report.part")).unwrap(),
+            b"unrelated"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_headers_reach_every_request() {
+        let body = payload(64 * 1024);
+        let server = TestServer::start(
+            ServerOptions::new(body.clone())
+                .require_header("authorization", "Bearer s3cret")
+                .require_header("user-agent", "fetcher/1.0"),
+        )
+        .await;
+        let output_path = temp_output("authorized.bin");
+
+        // Probe and segment requests all need the headers
+        let mut downloader = Downloader::builder(&server.url, &output_path)
+            .segments(3)
+            .bearer_auth("s3cret")
+            .user_agent(HeaderValue::from_static("fetcher/1.0"))
+            .build();
+        downloader.download().await.unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+
+        let url = format!("{}?token=s3cret", server.url);
+        let mut downloader = Downloader::builder(&url, temp_output("unauthorized.bin"))
+            .bearer_auth("wrong")
+            .build();
+        let err = downloader.download().await.unwrap_err();
+        assert!(matches!(
+            err.root(),
+            DownloadError::HttpStatus { code: 401, .. }
+        ));
+        assert!(!err.to_string().contains("s3cret"));
+
+        // Credentials that cannot be sent are reported, not silently left out
+        let mut downloader = Downloader::builder(&server.url, temp_output("bad_credentials.bin"))
+            .bearer_auth("s3cret\n")
+            .build();
+        let err = downloader.download().await.unwrap_err();
+        assert!(matches!(err.root(), DownloadError::InvalidConfig(_)));
+        assert!(!err.to_string().contains("s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_to_agreeing_mirror() {
+        let body = payload(24 * 1024);
+        let matching = TestServer::start(ServerOptions::new(body.clone()).etag("\"v1\"")).await;
+        let other_size = TestServer::start(ServerOptions::new(payload(1000))).await;
+        let output_path = temp_output("mirrored.bin");
+        let recorder = Arc::new(RecordingProgress::default());
+
+        // The primary is gone, and the second mirror holds a different file
+        let primary = format!("{}.missing", matching.url);
+        let mut downloader = Downloader::builder(&primary, &output_path)
+            .mirrors([&other_size.url, &matching.url])
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .build();
+
+        // Resume state recorded while the primary was still up
+        std::fs::write(downloader.temp_path(), &body[..5000]).unwrap();
+        let validators = Validators {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            total: Some(body.len() as u64),
+        };
+        downloader.save_part_meta(&validators, None).unwrap();
+
+        let outcome = downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Completed {
+                bytes: body.len() as u64,
+                resumed_from: 5000,
+            }
+        );
+        let events = recorder.events.lock().unwrap();
+        let switches: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::MirrorChanged { url } => Some(url.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(switches, [matching.url.as_str()]);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_mirrors_move_ranges_off_slow_mirror() {
+        let body = payload(512 * 1024);
+        let fast = TestServer::start(ServerOptions::new(body.clone()).etag("\"v1\"")).await;
+        // Alone, this mirror would need several seconds for even one of its ranges
+        let slow = TestServer::start(
+            ServerOptions::new(body.clone())
+                .etag("\"v1\"")
+                .throttle(1024, std::time::Duration::from_millis(100)),
+        )
+        .await;
+        let other_size = TestServer::start(ServerOptions::new(payload(1000))).await;
+        let output_path = temp_output("parallel_mirrors.bin");
+
+        let started = Instant::now();
+        let mut downloader = Downloader::builder(&fast.url, &output_path)
+            .mirrors([&slow.url, &other_size.url])
+            .parallel_mirrors(true)
+            .segments(2)
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_changed_mirror_does_not_discard_part() {
+        let body = payload(64 * 1024);
+        // The primary cuts off every range, so the mirror is left to fail last
+        let primary = TestServer::start(
+            ServerOptions::new(body.clone())
+                .etag("\"v1\"")
+                .truncate_after(0, usize::MAX),
+        )
+        .await;
+        // After its probe and first range, the mirror serves another version of the file
+        let mirror = TestServer::start(
+            ServerOptions::new(body.clone())
+                .etag("\"v1\"")
+                .revise_after(2, "\"v2\"")
+                .throttle(1024, std::time::Duration::from_millis(20)),
+        )
+        .await;
+        let output_path = temp_output("changed_mirror.bin");
+
+        let mut downloader = Downloader::builder(&primary.url, &output_path)
+            .mirrors([format!("{}?token=s3cret", mirror.url)])
+            .parallel_mirrors(true)
+            .segments(2)
+            .retry_policy(ExponentialBackoff::new().with_max_attempts(1))
+            .build();
+        let err = downloader.download().await.unwrap_err();
+
+        assert!(!matches!(err.root(), DownloadError::RemoteChanged));
+        assert!(err.to_string().contains("no longer serves"));
+        assert!(!err.to_string().contains("s3cret"));
+        let meta = PartMeta::load(&downloader.meta_path()).unwrap();
+        assert!(meta.segments.unwrap().downloaded() > 0);
+        assert!(downloader.temp_path().exists());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_is_followed_once_and_recorded() {
+        let body = payload(64 * 1024);
+        let target = TestServer::start(ServerOptions::new(body.clone()).etag("\"v1\"")).await;
+        let options = ServerOptions::new(Vec::new()).redirect_to(&target.url);
+        let redirector_hits = options.hits.clone();
+        let redirector = TestServer::start(options).await;
+        let output_path = temp_output("redirected.bin");
+
+        let mut downloader = Downloader::builder(&redirector.url, &output_path)
+            .segments(3)
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert_eq!(redirector_hits.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            downloader.resolved_url().as_deref(),
+            Some(target.url.as_str())
+        );
+        assert_eq!(downloader.redirects(), [target.url.as_str()]);
+    }
+
+    #[tokio::test]
+    async fn test_failing_resolved_url_is_resolved_again() {
+        let body = payload(24 * 1024);
+        let target = TestServer::start(ServerOptions::new(body.clone()).etag("\"v1\"")).await;
+        let redirector =
+            TestServer::start(ServerOptions::new(Vec::new()).redirect_to(&target.url)).await;
+        let output_path = temp_output("re_resolved.bin");
+        let recorder = Arc::new(RecordingProgress::default());
+
+        let mut downloader = Downloader::builder(&redirector.url, &output_path)
+            .retry_policy(
+                ExponentialBackoff::new()
+                    .with_max_attempts(2)
+                    .with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .progress(ProgressTracker::new(recorder.clone(), 0))
+            .build();
+
+        // An earlier run was sent to a signed URL that has since expired
+        std::fs::write(downloader.temp_path(), &body[..5000]).unwrap();
+        let validators = Validators {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            total: Some(body.len() as u64),
+        };
+        let expired = format!("{}.expired", target.url);
+        downloader.resolution.get_mut().unwrap().url = Some(expired);
+        downloader.save_part_meta(&validators, None).unwrap();
+        downloader.resolution.get_mut().unwrap().url = None;
+
+        let outcome = downloader.download().await.unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), body);
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Completed {
+                bytes: body.len() as u64,
+                resumed_from: 5000,
+            }
+        );
+        assert_eq!(
+            downloader.resolved_url().as_deref(),
+            Some(target.url.as_str())
+        );
+        // The 404 from the expired URL was charged as an attempt, though not retryable by itself
+        let events = recorder.events.lock().unwrap();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ProgressEvent::Retrying { attempt: 1, error, .. } if error.contains("404")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_policy_is_enforced() {
+        let target = TestServer::start(ServerOptions::new(payload(1000))).await;
+        let other_host = target.url.replace("127.0.0.1", "localhost");
+        let redirector =
+            TestServer::start(ServerOptions::new(Vec::new()).redirect_to(&other_host)).await;
+
+        for policy in [
+            RedirectPolicy::new().with_cross_host(false),
+            RedirectPolicy::new().with_max_redirects(0),
+        ] {
+            let mut downloader =
+                Downloader::builder(&redirector.url, temp_output("refused_redirect.bin"))
+                    .redirect_policy(policy)
+                    .build();
+            let error = downloader.download().await.unwrap_err();
+            assert!(matches!(error.root(), DownloadError::Redirect(_)));
+        }
+
+        // A client that follows redirects itself would bypass the policy
+        let mut downloader =
+            Downloader::builder(&redirector.url, temp_output("client_redirect.bin"))
+                .client(reqwest::Client::new())
+                .redirect_policy(RedirectPolicy::new().with_cross_host(false))
+                .build();
+        let error = downloader.download().await.unwrap_err();
+        assert!(matches!(error.root(), DownloadError::Redirect(_)));
+    }
+
+    /// Output that takes at most 700 bytes per write and fails each configured step once
+    #[derive(Default)]
+    struct FlakyWriter {
+        output: Arc<Mutex<Vec<u8>>>,
+        /// Writes fail once this many bytes were taken
+        fail_write_at: Option<usize>,
+        fail_flush: bool,
+    }
+
+    impl tokio::io::AsyncWrite for FlakyWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            let this = self.get_mut();
+            let mut output = this.output.lock().unwrap();
+            if this.fail_write_at.is_some_and(|at| output.len() >= at) {
+                this.fail_write_at = None;
+                return Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+            }
+            let taken = buf.len().min(700);
+            output.extend_from_slice(&buf[..taken]);
+            Poll::Ready(Ok(taken))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            let this = self.get_mut();
+            if std::mem::take(&mut this.fail_flush) {
+                return Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partly_written_chunk_is_resumed_after_taken_bytes() {
+        let body = payload(48 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone()).etag("\"v1\"")).await;
+
+        // The write fails after the writer took part of a chunk
+        let writer = FlakyWriter {
+            fail_write_at: Some(1000),
+            ..Default::default()
+        };
+        let output = writer.output.clone();
+        let mut downloader = Downloader::builder_to_sink(&server.url, WriterSink::new(writer))
+            .checksum(Checksum::sha256(sha256_of(&body)))
+            .retry_policy(
+                ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .build();
+        downloader.download().await.unwrap();
+
+        assert_eq!(*output.lock().unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_sink_holding_every_byte_is_finished() {
+        let body = payload(48 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone()).etag("\"v1\"")).await;
+
+        // Every byte is written before the attempt fails, so the retry is answered with 416
+        let writer = FlakyWriter {
+            fail_flush: true,
+            ..Default::default()
+        };
+        let output = writer.output.clone();
+        let mut downloader = Downloader::builder_to_sink(&server.url, WriterSink::new(writer))
+            .checksum(Checksum::sha256(sha256_of(&body)))
+            .retry_policy(
+                ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .build();
+        let outcome = downloader.download().await.unwrap();
+
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Completed {
+                bytes: body.len() as u64,
+                resumed_from: body.len() as u64,
+            }
+        );
+        assert_eq!(*output.lock().unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_memory_sink_resumes_within_download() {
+        let body = payload(48 * 1024);
+        let server = TestServer::start(
+            ServerOptions::new(body.clone())
+                .etag("\"v1\"")
+                .stall_after(10_000, 1),
+        )
+        .await;
+
+        let memory = MemorySink::new();
+        let mut downloader = Downloader::builder_to_sink(&server.url, memory.clone())
+            .checksum(Checksum::sha256(sha256_of(&body)))
+            .idle_timeout(std::time::Duration::from_millis(200))
+            .retry_policy(
+                ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10)),
+            )
+            .build();
+        let outcome = downloader.download().await.unwrap();
+
+        assert_eq!(
+            outcome,
+            DownloadOutcome::Completed {
+                bytes: body.len() as u64,
+                resumed_from: 10_000,
+            }
+        );
+        assert_eq!(memory.take(), body);
+    }
+
+    #[tokio::test]
+    async fn test_sink_restarts_only_if_it_can() {
+        let body = payload(48 * 1024);
+        // The first response stalls and the retry is answered from the first byte again
+        let server = || async {
+            let options = ServerOptions::new(body.clone())
+                .ignore_range()
+                .stall_after(10_000, 1);
+            TestServer::start(options).await
+        };
+        let retry =
+            || ExponentialBackoff::new().with_base_delay(std::time::Duration::from_millis(10));
+
+        let memory = MemorySink::new();
+        let mut downloader = Downloader::builder_to_sink(&server().await.url, memory.clone())
+            .idle_timeout(std::time::Duration::from_millis(200))
+            .retry_policy(retry())
+            .build();
+        downloader.download().await.unwrap();
+        assert_eq!(memory.take(), body);
+
+        let writer = WriterSink::new(Vec::new());
+        let mut downloader = Downloader::builder_to_sink(&server().await.url, writer)
+            .idle_timeout(std::time::Duration::from_millis(200))
+            .retry_policy(retry())
+            .build();
+        let error = downloader.download().await.unwrap_err();
+        let DownloadError::Io(e) = error.root() else {
+            panic!("expected an IO error, got {}", error);
+        };
+        assert_eq!(e.kind(), std::io::ErrorKind::Unsupported);
+    }
 }
@@ -1,17 +1,125 @@
+use crate::{checksum::ChecksumAlgorithm, redact};
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum DownloadError {
     #[error("HTTP error: {0}")]
-    Http(#[from] reqwest::Error),
+    Http(reqwest::Error),
+    #[error("HTTP {code} from {url}")]
+    HttpStatus { code: u16, url: String },
+    #[error("Request timed out: {0}")]
+    Timeout(reqwest::Error),
     #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
+    #[error("Not enough disk space: {0}")]
+    DiskFull(std::io::Error),
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Redirect refused: {0}")]
+    Redirect(String),
     #[error("Invalid range")]
     InvalidRange,
     #[error("Range Not Satisfiable (416)")]
     RangeNotSatisfiable,
     #[error("Unsupported Server")]
     UnsupportedServer,
+    #[error("Transfer stalled: {0}")]
+    Stalled(String),
+    #[error("Remote file changed since the part file was written")]
+    RemoteChanged,
+    #[error("Size mismatch: expected {expected} bytes, got {actual}")]
+    SizeMismatch { expected: u64, actual: u64 },
+    #[error("Download cancelled")]
+    Cancelled,
+    /// Another process holds the lock at `path`; [`Downloader::download`](crate::Downloader::download)
+    /// reports this as [`DownloadOutcome::LockedByOther`](crate::DownloadOutcome::LockedByOther)
+    #[error("Another process holds the download lock {}", path.display())]
+    LockHeld { path: PathBuf },
+    #[error("Checksum mismatch ({algorithm}): expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        algorithm: ChecksumAlgorithm,
+        expected: String,
+        actual: String,
+    },
+    /// Wraps every error returned by [`Downloader::download`](crate::Downloader::download).
+    ///
+    /// URLs in errors have their credentials masked.
+    #[error("{url} -> {} (attempt {attempt}): {source}", path.display())]
+    Context {
+        url: String,
+        path: PathBuf,
+        attempt: usize,
+        #[source]
+        source: Box<DownloadError>,
+    },
+}
+
+impl DownloadError {
+    /// The underlying error, without the request context
+    pub fn root(&self) -> &DownloadError {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Self::Context { url, .. } => Some(url),
+            Self::HttpStatus { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+
+    pub fn output_path(&self) -> Option<&Path> {
+        match self {
+            Self::Context { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn with_context(self, url: &str, path: &Path, attempt: usize) -> Self {
+        match self {
+            error @ Self::Context { .. } => error,
+            error => Self::Context {
+                url: redact::redacted(url),
+                path: path.to_path_buf(),
+                attempt,
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(mut error: reqwest::Error) -> Self {
+        if let Some(url) = error.url_mut() {
+            redact::redact_url(url);
+        }
+        if let Some(status) = error.status() {
+            return Self::HttpStatus {
+                code: status.as_u16(),
+                url: error.url().map(|url| url.to_string()).unwrap_or_default(),
+            };
+        }
+        if error.is_timeout() {
+            return Self::Timeout(error);
+        }
+        Self::Http(error)
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded => Self::DiskFull(error),
+            _ => Self::Io(error),
+        }
+    }
 }
@@ -0,0 +1,255 @@
+// filename.rs
+
+use percent_encoding::percent_decode_str;
+
+/// Name used when neither the response nor the URL yields a usable one
+pub const FALLBACK_NAME: &str = "download";
+
+/// Longest file name most file systems accept, in bytes
+const MAX_NAME_LEN: usize = 255;
+
+/// Extensions of the files kept next to a download, which a downloaded file must not end in
+const SIDECAR_EXTENSIONS: &[&str] = &["part", "meta", "corrupt", "tmp", "lock"];
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// File name for a response, from `Content-Disposition` or else the last segment of its URL
+pub fn resolve(content_disposition: Option<&str>, url: &reqwest::Url) -> String {
+    content_disposition
+        .and_then(from_content_disposition)
+        .and_then(|name| sanitize(&name))
+        .or_else(|| from_url(url).and_then(|name| sanitize(&name)))
+        .unwrap_or_else(|| FALLBACK_NAME.to_string())
+}
+
+/// File name from a `Content-Disposition` header, preferring RFC 6266 `filename*` over `filename`
+pub fn from_content_disposition(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+
+    for (name, value) in parameters(value) {
+        if name.eq_ignore_ascii_case("filename*") {
+            extended = extended.or_else(|| decode_ext_value(&value));
+        } else if name.eq_ignore_ascii_case("filename") {
+            plain = plain.or(Some(value));
+        }
+    }
+
+    extended.or(plain)
+}
+
+/// Last non-empty path segment of `url`, percent-decoded
+pub fn from_url(url: &reqwest::Url) -> Option<String> {
+    let segment = url
+        .path_segments()?
+        .rev()
+        .find(|segment| !segment.is_empty())?;
+    Some(percent_decode_str(segment).decode_utf8_lossy().into_owned())
+}
+
+/// Makes a remote-supplied name safe to join onto a directory.
+///
+/// Directory components, separators and characters invalid on common file systems are removed,
+/// and names Windows reserves for devices are prefixed. Returns `None` if nothing usable is left.
+pub fn sanitize(name: &str) -> Option<String> {
+    // Only the final component counts, whichever separator the server used
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*'))
+        .collect();
+    // Windows drops trailing dots and spaces, which would alias another name
+    let cleaned = cleaned.trim().trim_end_matches(['.', ' ']);
+    if cleaned.is_empty() || cleaned.chars().all(|c| c == '.') {
+        return None;
+    }
+
+    let stem = cleaned.split('.').next().unwrap_or(cleaned);
+    let mut cleaned = if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        format!("_{}", cleaned)
+    } else {
+        cleaned.to_string()
+    };
+
+    if cleaned.len() > MAX_NAME_LEN {
+        let mut end = MAX_NAME_LEN;
+        while !cleaned.is_char_boundary(end) {
+            end -= 1;
+        }
+        cleaned.truncate(end);
+    }
+    Some(cleaned)
+}
+
+/// Appends `.download` to a name that would pass for the part file or another sidecar
+pub fn avoid_sidecars(name: String) -> String {
+    let sidecar = name.rsplit_once('.').is_some_and(|(_, extension)| {
+        SIDECAR_EXTENSIONS
+            .iter()
+            .any(|sidecar| extension.eq_ignore_ascii_case(sidecar))
+    });
+    if sidecar {
+        format!("{}.download", name)
+    } else {
+        name
+    }
+}
+
+/// First of `name`, `name (1)`, `name (2)`, ... that is not `taken`, numbering before the
+/// extensions so `a.tar.gz` becomes `a (1).tar.gz`
+pub fn first_free(name: &str, mut taken: impl FnMut(&str) -> bool) -> String {
+    let split = name
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '.')
+        .map_or(name.len(), |(index, _)| index);
+    let (stem, extensions) = name.split_at(split);
+
+    let mut candidate = name.to_string();
+    let mut counter = 0;
+    while taken(&candidate) {
+        counter += 1;
+        candidate = format!("{} ({}){}", stem, counter, extensions);
+    }
+    candidate
+}
+
+/// `name=value` pairs after the disposition type, with quoted strings unescaped
+fn parameters(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = value.chars().peekable();
+
+    // Skip the disposition type
+    for c in chars.by_ref() {
+        if c == ';' {
+            break;
+        }
+    }
+
+    loop {
+        let name: String = chars
+            .by_ref()
+            .skip_while(|c| c.is_whitespace() || *c == ';')
+            .take_while(|c| *c != '=')
+            .collect();
+        if name.is_empty() {
+            break;
+        }
+
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+            // Anything between the closing quote and the next parameter is ignored
+            for c in chars.by_ref() {
+                if c == ';' {
+                    break;
+                }
+            }
+        } else {
+            for c in chars.by_ref() {
+                if c == ';' {
+                    break;
+                }
+                value.push(c);
+            }
+        }
+
+        params.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    params
+}
+
+/// Decodes an RFC 8187 `charset'language'percent-encoded` value
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    let bytes: Vec<u8> = percent_decode_str(encoded).collect();
+    if charset.eq_ignore_ascii_case("UTF-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("ISO-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_filename_wins() {
+        let header = "attachment; filename=\"fallback.bin\"; filename*=UTF-8''r%C3%A9sum%C3%A9.bin";
+        assert_eq!(
+            from_content_disposition(header).as_deref(),
+            Some("résumé.bin")
+        );
+        assert_eq!(
+            from_content_disposition("attachment; filename=\"a \\\"b\\\".txt\"").as_deref(),
+            Some("a \"b\".txt")
+        );
+        assert_eq!(
+            from_content_disposition("inline; filename*=ISO-8859-1'en'%A3%20rates.txt").as_deref(),
+            Some("£ rates.txt")
+        );
+    }
+
+    #[test]
+    fn unsafe_names_are_sanitized() {
+        assert_eq!(sanitize("../../etc/passwd").as_deref(), Some("passwd"));
+        assert_eq!(sanitize("..\\..\\boot.ini").as_deref(), Some("boot.ini"));
+        assert_eq!(sanitize("CON.txt").as_deref(), Some("_CON.txt"));
+        assert_eq!(sanitize("report?.pdf. ").as_deref(), Some("report.pdf"));
+        assert_eq!(sanitize(".."), None);
+        assert_eq!(sanitize("dir/"), None);
+
+        let url = reqwest::Url::parse("http://example.com/files/my%20file.tar.gz?x=1").unwrap();
+        assert_eq!(resolve(None, &url), "my file.tar.gz");
+        let root = reqwest::Url::parse("http://example.com/").unwrap();
+        assert_eq!(resolve(Some("attachment"), &root), FALLBACK_NAME);
+    }
+
+    #[test]
+    fn names_avoid_existing_files_and_sidecars() {
+        assert_eq!(avoid_sidecars("data.part".into()), "data.part.download");
+        assert_eq!(
+            avoid_sidecars("data.part.META".into()),
+            "data.part.META.download"
+        );
+        assert_eq!(avoid_sidecars("report.pdf".into()), "report.pdf");
+
+        let taken = ["a.tar.gz", "a (1).tar.gz"];
+        assert_eq!(
+            first_free("a.tar.gz", |name| taken.contains(&name)),
+            "a (2).tar.gz"
+        );
+        assert_eq!(
+            first_free(".profile", |name| name == ".profile"),
+            ".profile (1)"
+        );
+        assert_eq!(first_free("free.bin", |_| false), "free.bin");
+    }
+}
@@ -1,6 +1,36 @@
+pub mod builder;
+pub mod checksum;
+pub mod control;
 pub mod downloader;
 pub mod error;
+pub mod filename;
+pub mod manager;
+pub mod meta;
+mod mirror_pool;
 pub mod progress;
+pub mod rate_limit;
+pub mod redact;
+pub mod redirect;
+pub mod retry;
+pub mod segment;
+pub mod sink;
+pub mod stall;
+pub mod transport;
+pub mod validators;
+mod writer;
 
-pub use downloader::{Downloader, ProgressTracker};
+#[cfg(test)]
+mod test_server;
+
+pub use builder::DownloaderBuilder;
+pub use checksum::{Checksum, ChecksumAlgorithm, Digest};
+pub use control::DownloadHandle;
+pub use downloader::{DownloadOutcome, Downloader, ProgressTracker};
 pub use error::DownloadError;
+pub use manager::{DownloadManager, JobHandle, JobId};
+pub use progress::{ProgressEvent, ProgressManager};
+pub use rate_limit::RateLimiter;
+pub use redirect::RedirectPolicy;
+pub use retry::{ExponentialBackoff, RetryPolicy};
+pub use sink::{MemorySink, Sink, WriterSink};
+pub use transport::TransportConfig;
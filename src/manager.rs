@@ -0,0 +1,416 @@
+// manager.rs
+
+use crate::{
+    builder::DownloaderBuilder,
+    control::DownloadHandle,
+    downloader::{DownloadOutcome, ProgressTracker},
+    error::DownloadError,
+    progress::ProgressManager,
+};
+use reqwest::header::HeaderMap;
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+};
+use tokio::sync::oneshot;
+
+/// Identifies a job submitted to a [`DownloadManager`]
+pub type JobId = u64;
+
+// =====================================
+// DownloadManager
+// =====================================
+
+/// Queue that runs downloads with bounded concurrency, globally and per host.
+///
+/// Queued jobs start in order of descending priority, then submission order. A job that does not
+/// have a progress tracker gets its own progress line when it starts.
+#[derive(Clone)]
+pub struct DownloadManager {
+    inner: Arc<ManagerInner>,
+}
+
+struct ManagerInner {
+    state: Mutex<QueueState>,
+}
+
+struct QueueState {
+    max_concurrent: usize,
+    max_per_host: usize,
+    progress: Option<Arc<dyn ProgressManager + Send + Sync>>,
+    headers: HeaderMap,
+    queue: Vec<QueuedJob>,
+    running: usize,
+    running_per_host: HashMap<String, usize>,
+    next_id: JobId,
+}
+
+struct QueuedJob {
+    id: JobId,
+    priority: i32,
+    host: String,
+    builder: DownloaderBuilder,
+    result_tx: oneshot::Sender<Result<DownloadOutcome, DownloadError>>,
+}
+
+impl DownloadManager {
+    /// Runs at most `max_concurrent` downloads at once
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            inner: Arc::new(ManagerInner {
+                state: Mutex::new(QueueState {
+                    max_concurrent: max_concurrent.max(1),
+                    max_per_host: usize::MAX,
+                    progress: None,
+                    headers: HeaderMap::new(),
+                    queue: Vec::new(),
+                    running: 0,
+                    running_per_host: HashMap::new(),
+                    next_id: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Runs at most `max_per_host` downloads against the same host at once
+    pub fn with_max_per_host(self, max_per_host: usize) -> Self {
+        self.inner.state.lock().unwrap().max_per_host = max_per_host.max(1);
+        self
+    }
+
+    /// Registers a line on `progress` for every job that starts without its own tracker
+    pub fn with_progress(self, progress: Arc<dyn ProgressManager + Send + Sync>) -> Self {
+        self.inner.state.lock().unwrap().progress = Some(progress);
+        self
+    }
+
+    /// Sends `headers` with every job, unless the job sets the same header itself
+    pub fn with_default_headers(self, headers: HeaderMap) -> Self {
+        self.inner.state.lock().unwrap().headers = headers;
+        self
+    }
+
+    /// Changes the global limit; running jobs are never interrupted
+    pub fn set_max_concurrent(&self, max_concurrent: usize) {
+        self.inner.state.lock().unwrap().max_concurrent = max_concurrent.max(1);
+        ManagerInner::pump(&self.inner);
+    }
+
+    /// Queues a download with priority 0
+    pub fn submit(&self, builder: DownloaderBuilder) -> JobHandle {
+        self.submit_with_priority(builder, 0)
+    }
+
+    /// Queues a download; jobs with a higher `priority` start first
+    pub fn submit_with_priority(&self, mut builder: DownloaderBuilder, priority: i32) -> JobHandle {
+        let (result_tx, result_rx) = oneshot::channel();
+        let control = builder.control_handle();
+        let host = host_of(builder.url());
+
+        let id = {
+            let mut state = self.inner.state.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.queue.push(QueuedJob {
+                id,
+                priority,
+                host,
+                builder,
+                result_tx,
+            });
+            id
+        };
+
+        ManagerInner::pump(&self.inner);
+
+        JobHandle {
+            id,
+            manager: self.clone(),
+            control,
+            result_rx,
+        }
+    }
+
+    /// Changes the priority of a job that has not started yet; returns false otherwise
+    pub fn set_priority(&self, id: JobId, priority: i32) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        match state.queue.iter_mut().find(|job| job.id == id) {
+            Some(job) => {
+                job.priority = priority;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels a job, removing it from the queue if it has not started yet
+    fn cancel(&self, id: JobId, control: &DownloadHandle) {
+        control.cancel();
+
+        let job = {
+            let mut state = self.inner.state.lock().unwrap();
+            let index = state.queue.iter().position(|job| job.id == id);
+            index.map(|index| state.queue.remove(index))
+        };
+        if let Some(job) = job {
+            let error = DownloadError::Cancelled.with_context(
+                job.builder.url(),
+                job.builder.output_path(),
+                0,
+            );
+            let _ = job.result_tx.send(Err(error));
+        }
+    }
+
+    /// Number of jobs waiting to start
+    pub fn queued(&self) -> usize {
+        self.inner.state.lock().unwrap().queue.len()
+    }
+
+    /// Number of jobs currently downloading
+    pub fn running(&self) -> usize {
+        self.inner.state.lock().unwrap().running
+    }
+}
+
+impl ManagerInner {
+    /// Starts queued jobs until a concurrency limit is reached
+    fn pump(inner: &Arc<ManagerInner>) {
+        let mut state = inner.state.lock().unwrap();
+
+        while state.running < state.max_concurrent {
+            let next = state
+                .queue
+                .iter()
+                .enumerate()
+                .filter(|(_, job)| {
+                    state.running_per_host.get(&job.host).copied().unwrap_or(0) < state.max_per_host
+                })
+                // Highest priority first, then earliest submission
+                .max_by(|(_, a), (_, b)| a.priority.cmp(&b.priority).then(b.id.cmp(&a.id)))
+                .map(|(index, _)| index);
+
+            let Some(index) = next else {
+                break;
+            };
+
+            let mut job = state.queue.remove(index);
+            job.builder.default_headers(&state.headers);
+            state.running += 1;
+            *state.running_per_host.entry(job.host.clone()).or_insert(0) += 1;
+
+            if let Some(ref progress) = state.progress {
+                if !job.builder.has_progress() {
+                    let line = progress.register();
+                    job.builder = job
+                        .builder
+                        .progress(ProgressTracker::new(progress.clone(), line));
+                }
+            }
+
+            tokio::spawn(ManagerInner::run(inner.clone(), job));
+        }
+    }
+
+    async fn run(inner: Arc<ManagerInner>, job: QueuedJob) {
+        let QueuedJob {
+            host,
+            builder,
+            result_tx,
+            ..
+        } = job;
+
+        let slot = Slot { inner, host };
+
+        let mut downloader = builder.build();
+        let result = downloader.download().await;
+        // Freed before the handle resolves, so its owner sees the slot available
+        drop(slot);
+        // The handle may have been dropped; the job still counts as finished
+        let _ = result_tx.send(result);
+    }
+}
+
+/// Concurrency slots of a running job, freed when it ends, even by panicking
+struct Slot {
+    inner: Arc<ManagerInner>,
+    host: String,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        {
+            let mut state = self
+                .inner
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            state.running -= 1;
+            if let Some(count) = state.running_per_host.get_mut(&self.host) {
+                *count -= 1;
+                if *count == 0 {
+                    state.running_per_host.remove(&self.host);
+                }
+            }
+        }
+
+        ManagerInner::pump(&self.inner);
+    }
+}
+
+fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+// =====================================
+// JobHandle
+// =====================================
+
+/// Resolves to the job's result when awaited
+pub struct JobHandle {
+    id: JobId,
+    manager: DownloadManager,
+    control: DownloadHandle,
+    result_rx: oneshot::Receiver<Result<DownloadOutcome, DownloadError>>,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// Moves the job within the queue; returns false once it has started
+    pub fn set_priority(&self, priority: i32) -> bool {
+        self.manager.set_priority(self.id, priority)
+    }
+
+    /// Handle controlling the job's download, valid before and after it starts
+    pub fn control(&self) -> &DownloadHandle {
+        &self.control
+    }
+
+    /// Drops the job if it is still queued, otherwise cancels its download
+    pub fn cancel(&self) {
+        self.manager.cancel(self.id, &self.control);
+    }
+}
+
+impl Future for JobHandle {
+    type Output = Result<DownloadOutcome, DownloadError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result_rx).poll(cx).map(|result| {
+            result.unwrap_or_else(|_| {
+                Err(DownloadError::InvalidResponse(
+                    "Download task ended without reporting a result".to_string(),
+                ))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::ProgressEvent;
+    use crate::test_server::{payload, temp_output, ServerOptions, TestServer};
+    use std::path::Path;
+
+    #[derive(Default)]
+    struct StartOrder {
+        titles: Mutex<Vec<String>>,
+    }
+
+    impl ProgressManager for StartOrder {
+        fn register(&self) -> usize {
+            0
+        }
+
+        fn update(&self, _line: usize, _content: &str) {}
+
+        fn on_event(&self, _line: usize, title: &str, event: &ProgressEvent) {
+            if *event == ProgressEvent::Started {
+                self.titles.lock().unwrap().push(title.to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_jobs_start_by_priority() {
+        let body = payload(256 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let order = Arc::new(StartOrder::default());
+        let manager = DownloadManager::new(1).with_progress(order.clone());
+
+        let job = |name: &str| {
+            DownloaderBuilder::new(&server.url, temp_output(&format!("manager_{}.bin", name)))
+                .title(name)
+        };
+        let first = manager.submit(job("first"));
+        let low = manager.submit(job("low"));
+        let high = manager.submit(job("high"));
+        assert_eq!(manager.queued(), 2);
+        assert!(high.set_priority(10));
+        assert!(!first.set_priority(10));
+
+        for handle in [first, low, high] {
+            handle.await.unwrap();
+        }
+        assert_eq!(*order.titles.lock().unwrap(), ["first", "high", "low"]);
+        assert_eq!(manager.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn per_host_limit_queues_same_host_only() {
+        let body = payload(64 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let manager = DownloadManager::new(4).with_max_per_host(1);
+
+        let job = |url: &str, name: &str| {
+            DownloaderBuilder::new(url, temp_output(&format!("per_host_{}.bin", name)))
+        };
+        let other_host = server.url.replace("127.0.0.1", "localhost");
+        let handles = [
+            manager.submit(job(&server.url, "a")),
+            manager.submit(job(&server.url, "b")),
+            manager.submit(job(&other_host, "c")),
+        ];
+        assert_eq!(manager.running(), 2);
+        assert_eq!(manager.queued(), 1);
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(manager.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn cancelled_job_leaves_the_queue() {
+        let body = payload(64 * 1024);
+        let server = TestServer::start(ServerOptions::new(body.clone())).await;
+        let manager = DownloadManager::new(1);
+
+        let running = manager.submit(DownloaderBuilder::new(
+            &server.url,
+            temp_output("queue_running.bin"),
+        ));
+        let queued = manager.submit(DownloaderBuilder::new(
+            &server.url,
+            temp_output("queue_cancelled.bin"),
+        ));
+        queued.cancel();
+        assert_eq!(manager.queued(), 0);
+
+        let err = queued.await.unwrap_err();
+        assert!(matches!(err.root(), DownloadError::Cancelled));
+        running.await.unwrap();
+        assert!(!Path::new(&temp_output("queue_cancelled.bin")).exists());
+        assert_eq!(manager.running(), 0);
+    }
+}
@@ -0,0 +1,137 @@
+// meta.rs
+
+use crate::{checksum::Checksum, segment::SegmentMap, validators::Validators};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Bumped whenever the layout of [`PartMeta`] changes incompatibly
+pub const META_VERSION: u32 = 1;
+
+/// Everything needed to decide whether a part file can be resumed, stored as `<name>.part.meta`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartMeta {
+    pub version: u32,
+    /// URL the part file was downloaded from
+    pub url: String,
+    pub validators: Validators,
+    /// Expected checksums the download was started with; digests are recomputed on resume
+    #[serde(default)]
+    pub checksums: Vec<Checksum>,
+    /// Present when the part file is filled by several connections at their own offsets
+    #[serde(default)]
+    pub segments: Option<SegmentMap>,
+    /// Where `url` last redirected to, requested directly until it stops working
+    #[serde(default)]
+    pub resolved_url: Option<String>,
+}
+
+impl PartMeta {
+    pub fn new(url: &str, validators: Validators, checksums: &[Checksum]) -> Self {
+        Self {
+            version: META_VERSION,
+            url: url.to_string(),
+            validators,
+            checksums: checksums.to_vec(),
+            segments: None,
+            resolved_url: None,
+        }
+    }
+
+    /// Reads metadata previously written by [`PartMeta::save`], rejecting unknown versions and
+    /// segment maps that do not describe the file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read(path)?;
+        let meta: Self = serde_json::from_slice(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        if meta.version != META_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported part metadata version {}", meta.version),
+            ));
+        }
+        if let Some(ref map) = meta.segments {
+            map.validate().map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid segment map: {}", e),
+                )
+            })?;
+        }
+        Ok(meta)
+    }
+
+    /// Writes the metadata through a temporary file so a crash never leaves it half written
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = serde_json::to_vec_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let tmp_path = path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(&contents)?;
+        drop(file);
+        fs::rename(&tmp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = std::env::temp_dir().join("part_meta_round_trip.part.meta");
+        let mut meta = PartMeta::new(
+            "http://example.com/a.bin",
+            Validators {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: None,
+                total: Some(1000),
+            },
+            &[Checksum::md5("900150983cd24fb0d6963f7d28e17f72")],
+        );
+        let mut map = SegmentMap::plan(1000, 4);
+        map.segments[1].downloaded = 42;
+        meta.segments = Some(map);
+
+        meta.save(&path).unwrap();
+        let loaded = PartMeta::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(loaded, meta);
+    }
+
+    #[test]
+    fn load_rejects_other_versions() {
+        let path = std::env::temp_dir().join("part_meta_version.part.meta");
+        let mut meta = PartMeta::new("http://example.com/a.bin", Validators::default(), &[]);
+        meta.version = META_VERSION + 1;
+
+        meta.save(&path).unwrap();
+        let err = PartMeta::load(&path).unwrap_err();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn load_rejects_damaged_segment_maps() {
+        let path = std::env::temp_dir().join("part_meta_segments.part.meta");
+        let mut meta = PartMeta::new("http://example.com/a.bin", Validators::default(), &[]);
+        // Nothing left to fetch would pass any part file of the right length as finished
+        meta.segments = Some(SegmentMap {
+            total: 1000,
+            segments: Vec::new(),
+        });
+
+        meta.save(&path).unwrap();
+        let err = PartMeta::load(&path).unwrap_err();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,216 @@
+// mirror_pool.rs
+
+use crate::{error::DownloadError, segment::SegmentMap, validators::Validators};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// Segments planned per connection, so that faster mirrors can take more of them
+pub(crate) const PIECES_PER_CONNECTION: usize = 4;
+
+/// Shortest time a mirror holds a segment before its throughput is judged
+const MIN_JUDGE_TIME: Duration = Duration::from_millis(500);
+
+/// How often an idle connection looks for a slow mirror to take a segment from
+pub(crate) const RECHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An idle mirror this many times faster than a busy one takes over its segment
+const SLOW_FACTOR: f64 = 4.0;
+
+/// A mirror that serves ranges of the file
+pub(crate) struct MirrorSource {
+    pub url: String,
+    /// Validators this mirror reported, used for its `If-Range` requests
+    pub validators: Validators,
+}
+
+struct Claim {
+    mirror: usize,
+    started: Instant,
+    start_bytes: u64,
+    revoke: Arc<Notify>,
+}
+
+#[derive(Default)]
+struct MirrorStats {
+    bytes: u64,
+    busy: Duration,
+    failed: bool,
+}
+
+/// Hands out segments to the connections of several mirrors.
+///
+/// Connections take the next free segment as they finish the last one, so each mirror's share
+/// follows its throughput. An idle mirror takes over the segment of a much slower one, and a mirror
+/// whose request fails serves nothing more.
+pub(crate) struct MirrorPool {
+    stats: Vec<MirrorStats>,
+    claims: HashMap<usize, Claim>,
+    /// Segments taken away from a mirror, as `(segment, mirror)`
+    stolen: HashSet<(usize, usize)>,
+    last_error: Option<DownloadError>,
+}
+
+/// What a connection should do next
+pub(crate) enum Assignment {
+    Fetch { index: usize, revoke: Arc<Notify> },
+    Wait,
+    Done,
+}
+
+impl MirrorPool {
+    pub fn new(mirrors: usize) -> Self {
+        Self {
+            stats: (0..mirrors).map(|_| MirrorStats::default()).collect(),
+            claims: HashMap::new(),
+            stolen: HashSet::new(),
+            last_error: None,
+        }
+    }
+
+    /// Next segment for a connection to `mirror`, taking one over from a slow mirror if needed
+    pub fn assign(&mut self, map: &SegmentMap, mirror: usize) -> Assignment {
+        if self.stats[mirror].failed || map.is_complete() {
+            return Assignment::Done;
+        }
+
+        // Without another working mirror, nothing may stay off limits
+        let others_alive = self
+            .stats
+            .iter()
+            .enumerate()
+            .any(|(other, stats)| other != mirror && !stats.failed);
+
+        let off_limits = |index: &usize| others_alive && self.stolen.contains(&(*index, mirror));
+        let free = (0..map.segments.len()).find(|index| {
+            !map.segments[*index].is_complete()
+                && !self.claims.contains_key(index)
+                && !off_limits(index)
+        });
+        if let Some(index) = free {
+            let revoke = Arc::new(Notify::new());
+            self.claims.insert(
+                index,
+                Claim {
+                    mirror,
+                    started: Instant::now(),
+                    start_bytes: map.segments[index].downloaded,
+                    revoke: revoke.clone(),
+                },
+            );
+            return Assignment::Fetch { index, revoke };
+        }
+
+        self.steal(map, mirror);
+        Assignment::Wait
+    }
+
+    /// Ends a claim, crediting its mirror with the bytes fetched meanwhile
+    pub fn release(&mut self, map: &SegmentMap, index: usize, result: Result<(), DownloadError>) {
+        let Some(claim) = self.claims.remove(&index) else {
+            return;
+        };
+        let stats = &mut self.stats[claim.mirror];
+        stats.bytes += map.segments[index].downloaded - claim.start_bytes;
+        stats.busy += claim.started.elapsed();
+
+        if let Err(e) = result {
+            stats.failed = true;
+            self.last_error = Some(e);
+        }
+    }
+
+    /// Error of the last failed mirror
+    pub fn into_error(self) -> Option<DownloadError> {
+        self.last_error
+    }
+
+    /// Revokes the segment of the slowest mirror that `mirror` outpaces by `SLOW_FACTOR`
+    fn steal(&mut self, map: &SegmentMap, mirror: usize) {
+        let Some(rate) = self.rate(mirror) else {
+            return;
+        };
+
+        let slowest = self
+            .claims
+            .iter()
+            .filter(|(index, claim)| {
+                claim.mirror != mirror
+                    && claim.started.elapsed() >= MIN_JUDGE_TIME
+                    && !self.stolen.contains(&(**index, claim.mirror))
+            })
+            .map(|(index, claim)| {
+                let fetched = map.segments[*index].downloaded - claim.start_bytes;
+                (
+                    *index,
+                    fetched as f64 / claim.started.elapsed().as_secs_f64(),
+                )
+            })
+            .filter(|(_, claim_rate)| claim_rate * SLOW_FACTOR < rate)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        if let Some((index, _)) = slowest {
+            let claim = &self.claims[&index];
+            self.stolen.insert((index, claim.mirror));
+            claim.revoke.notify_one();
+        }
+    }
+
+    /// Bytes per second over the segments `mirror` has finished or given up
+    fn rate(&self, mirror: usize) -> Option<f64> {
+        let stats = &self.stats[mirror];
+        (stats.bytes > 0 && !stats.busy.is_zero())
+            .then(|| stats.bytes as f64 / stats.busy.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetch(assignment: Assignment) -> usize {
+        match assignment {
+            Assignment::Fetch { index, .. } => index,
+            _ => panic!("expected a segment"),
+        }
+    }
+
+    #[test]
+    fn idle_mirror_takes_over_from_slow_one() {
+        let mut map = SegmentMap::plan(4000, 2);
+        let mut pool = MirrorPool::new(2);
+
+        let slow = fetch(pool.assign(&map, 1));
+        let fast = fetch(pool.assign(&map, 0));
+        map.segments[fast].downloaded = map.segments[fast].len();
+        pool.release(&map, fast, Ok(()));
+
+        // Backdate the slow claim so it can be judged; it fetched nothing meanwhile
+        pool.claims.get_mut(&slow).unwrap().started -= MIN_JUDGE_TIME;
+        assert!(matches!(pool.assign(&map, 0), Assignment::Wait));
+        assert!(pool.stolen.contains(&(slow, 1)));
+
+        pool.release(&map, slow, Ok(()));
+        assert!(matches!(pool.assign(&map, 1), Assignment::Wait));
+        assert_eq!(fetch(pool.assign(&map, 0)), slow);
+    }
+
+    #[test]
+    fn failed_mirror_gets_nothing_more() {
+        let map = SegmentMap::plan(4000, 4);
+        let mut pool = MirrorPool::new(2);
+
+        let index = fetch(pool.assign(&map, 1));
+        pool.release(&map, index, Err(DownloadError::RemoteChanged));
+
+        assert!(matches!(pool.assign(&map, 1), Assignment::Done));
+        assert_eq!(fetch(pool.assign(&map, 0)), index);
+        assert!(matches!(
+            pool.into_error(),
+            Some(DownloadError::RemoteChanged)
+        ));
+    }
+}
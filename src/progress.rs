@@ -4,6 +4,128 @@ use crossterm::terminal::size;
 use regex::Regex;
 use std::io::{self, Write};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const MAX_TITLE_WIDTH: usize = 30;
+
+// =====================================
+// ProgressEvent
+// =====================================
+
+/// Lifecycle and throughput events emitted by a download
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    Started,
+    /// Remote size is known (`None` when the server does not report it)
+    Probed {
+        total: Option<u64>,
+    },
+    /// `speed` is in bytes per second, once a full measurement window has elapsed
+    Bytes {
+        downloaded: u64,
+        total: Option<u64>,
+        speed: Option<f64>,
+    },
+    Retrying {
+        attempt: usize,
+        delay: Duration,
+        error: String,
+    },
+    /// The final file was already complete
+    Skipped,
+    /// Another process holds the download lock
+    LockHeld,
+    /// The download moved to another mirror; `url` has credentials masked
+    MirrorChanged {
+        url: String,
+    },
+    /// Reading stopped through a [`DownloadHandle`](crate::DownloadHandle); the part file is kept
+    Paused,
+    Resumed,
+    Finished,
+    /// Stopped through a [`DownloadHandle`](crate::DownloadHandle); the part file is kept
+    Cancelled,
+    Failed {
+        error: String,
+    },
+}
+
+/// Converts bytes to megabytes
+fn bytes_to_mb(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+/// Truncates title to fit within display width
+fn truncated_title(title: &str) -> String {
+    if title.chars().count() > MAX_TITLE_WIDTH {
+        let mut truncated = title.chars().take(MAX_TITLE_WIDTH - 1).collect::<String>();
+        truncated.push('…');
+        truncated
+    } else {
+        title.to_string()
+    }
+}
+
+impl ProgressEvent {
+    /// Formats the event as a single progress line
+    pub fn render(&self, title: &str) -> String {
+        let title = truncated_title(title);
+
+        match self {
+            Self::Started => format!("Starting {}", title),
+            Self::Probed { total: Some(total) } => {
+                format!("Starting {}: {:.2} MB", title, bytes_to_mb(*total))
+            }
+            Self::Probed { total: None } => format!("Starting {}", title),
+            Self::Bytes {
+                downloaded,
+                total,
+                speed,
+            } => {
+                let speed_message = speed
+                    .map(|speed| format!(" | {:.2} MB/s", bytes_to_mb(speed as u64)))
+                    .unwrap_or_default();
+
+                match total {
+                    Some(total) => format!(
+                        "Downloading {}: {:.2} MB / {:.2} MB ({:.2}%){}",
+                        title,
+                        bytes_to_mb(*downloaded),
+                        bytes_to_mb(*total),
+                        (*downloaded as f64 / *total as f64) * 100.0,
+                        speed_message
+                    ),
+                    None => format!(
+                        "Downloaded {}: {:.2} MB{}",
+                        title,
+                        bytes_to_mb(*downloaded),
+                        speed_message
+                    ),
+                }
+            }
+            Self::Retrying {
+                attempt,
+                delay,
+                error,
+            } => format!(
+                "Retrying {} (attempt {}) in {:.1}s: {}",
+                title,
+                attempt,
+                delay.as_secs_f64(),
+                error
+            ),
+            Self::Skipped => format!("File already complete: {} — skipping download", title),
+            Self::LockHeld => "Another instance is downloading — aborting".to_string(),
+            Self::MirrorChanged { url } => format!("Switching {} to mirror {}", title, url),
+            Self::Paused => format!("Paused {}", title),
+            Self::Resumed => format!("Resuming {}", title),
+            Self::Finished => format!("Finished {}", title),
+            Self::Cancelled => format!("Cancelled {}", title),
+            Self::Failed { error } => format!("Failed {}: {}", title, error),
+        }
+    }
+}
 
 // =====================================
 // ProgressManager trait
@@ -15,6 +137,11 @@ pub trait ProgressManager: Send + Sync {
 
     /// Update the content of a specific line
     fn update(&self, line: usize, content: &str);
+
+    /// Receive a typed event for a line; by default it is rendered and passed to `update`
+    fn on_event(&self, line: usize, title: &str, event: &ProgressEvent) {
+        self.update(line, &event.render(title));
+    }
 }
 
 // =====================================
@@ -41,7 +168,7 @@ fn truncate_ansi(s: &str, max_visible: usize) -> String {
             out.push(ch);
             iter.next();
 
-            while let Some(c) = iter.next() {
+            for c in iter.by_ref() {
                 out.push(c);
                 if c.is_ascii_alphabetic() {
                     break;
@@ -122,6 +249,10 @@ impl LineBuffer {
         state.lines.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn resize(&self, new_size: usize) {
         let mut state = self.inner.lock().unwrap();
 
@@ -178,6 +309,12 @@ impl StdoutProgressManager {
     }
 }
 
+impl Default for StdoutProgressManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ProgressManager for StdoutProgressManager {
     fn register(&self) -> usize {
         let mut state = self.inner.lock().unwrap();
@@ -209,4 +346,32 @@ impl ProgressManager for NullProgressManager {
     fn update(&self, _line: usize, _content: &str) {
         // Do nothing
     }
+
+    fn on_event(&self, _line: usize, _title: &str, _event: &ProgressEvent) {
+        // Skip rendering entirely
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_bytes_matches_legacy_format() {
+        let event = ProgressEvent::Bytes {
+            downloaded: 1024 * 1024,
+            total: Some(2 * 1024 * 1024),
+            speed: Some(3.0 * 1024.0 * 1024.0),
+        };
+        assert_eq!(
+            event.render("x"),
+            "Downloading x: 1.00 MB / 2.00 MB (50.00%) | 3.00 MB/s"
+        );
+    }
+
+    #[test]
+    fn render_truncates_long_titles() {
+        let line = ProgressEvent::Finished.render(&"a".repeat(40));
+        assert_eq!(line, format!("Finished {}…", "a".repeat(29)));
+    }
 }
@@ -0,0 +1,130 @@
+// rate_limit.rs
+
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Token bucket shared by every download that draws from it.
+///
+/// Wrap it in an `Arc` and hand it to several downloaders to keep their aggregate rate under one
+/// ceiling. The rate can be changed at any time with [`RateLimiter::set_rate`].
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes per second, `None` when unlimited
+    rate: Option<u64>,
+    /// Available bytes; negative while callers are paying off a burst
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            // Allow at most one second of burst
+            self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        }
+        self.last_refill = now;
+    }
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                rate: Some(bytes_per_second.max(1)),
+                tokens: bytes_per_second as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                rate: None,
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Current limit in bytes per second, `None` when unlimited
+    pub fn rate(&self) -> Option<u64> {
+        self.bucket.lock().unwrap().rate
+    }
+
+    /// Changes the limit; `None` lifts it. Takes effect for every download sharing the limiter.
+    pub fn set_rate(&self, bytes_per_second: Option<u64>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+        bucket.rate = bytes_per_second.map(|rate| rate.max(1));
+        if let Some(rate) = bucket.rate {
+            bucket.tokens = bucket.tokens.min(rate as f64);
+        } else {
+            bucket.tokens = 0.0;
+        }
+    }
+
+    /// Takes `bytes` from the bucket, sleeping until the debt they create is paid off
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            bucket.refill();
+
+            let Some(rate) = bucket.rate else {
+                return;
+            };
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / rate as f64)
+        };
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn shared_limiter_caps_aggregate_rate() {
+        let limiter = Arc::new(RateLimiter::new(1_000_000));
+        let started = Instant::now();
+
+        // One second of burst plus 500 KB drawn by two concurrent consumers
+        let consumers = (0..2).map(|_| {
+            let limiter = limiter.clone();
+            tokio::spawn(async move {
+                for _ in 0..15 {
+                    limiter.acquire(50_000).await;
+                }
+            })
+        });
+        for consumer in consumers {
+            consumer.await.unwrap();
+        }
+
+        assert!(started.elapsed() >= Duration::from_millis(450));
+    }
+
+    #[tokio::test]
+    async fn unlimited_never_waits() {
+        let limiter = RateLimiter::new(10);
+        limiter.set_rate(None);
+
+        let started = Instant::now();
+        limiter.acquire(1_000_000).await;
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert_eq!(limiter.rate(), None);
+    }
+}
@@ -0,0 +1,202 @@
+// redirect.rs
+
+use crate::{error::DownloadError, redact};
+use reqwest::{
+    header::{HeaderMap, HeaderName, AUTHORIZATION, COOKIE, LOCATION, PROXY_AUTHORIZATION},
+    Response, StatusCode, Url,
+};
+
+/// Which redirects the downloader follows on its way to the file.
+///
+/// Redirects are followed by the downloader rather than the client, so that the resolved URL can
+/// be reused by later requests and retries. Clients from
+/// [`TransportConfig::build_client`](crate::TransportConfig::build_client) and the default client
+/// leave redirects to it. A request that a client redirected on its own, out of the policy's
+/// sight, fails with [`DownloadError::Redirect`].
+#[derive(Clone, Debug)]
+pub struct RedirectPolicy {
+    max_redirects: usize,
+    allow_downgrade: bool,
+    allow_cross_host: bool,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_redirects: 10,
+            allow_downgrade: false,
+            allow_cross_host: true,
+        }
+    }
+}
+
+impl RedirectPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Most redirects followed for one request; 0 refuses every redirect
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Whether an `https` URL may redirect to plain `http`; refused by default
+    pub fn with_https_downgrade(mut self, allow: bool) -> Self {
+        self.allow_downgrade = allow;
+        self
+    }
+
+    /// Whether a redirect may lead to another host; allowed by default, as CDNs usually do
+    pub fn with_cross_host(mut self, allow: bool) -> Self {
+        self.allow_cross_host = allow;
+        self
+    }
+
+    /// Where `response` redirects to, or `None` if it is not a redirect to follow.
+    ///
+    /// `origin` is the URL the request started at and `followed` the redirects taken so far.
+    pub(crate) fn next_hop(
+        &self,
+        origin: &Url,
+        response: &Response,
+        followed: usize,
+    ) -> Result<Option<Url>, DownloadError> {
+        let redirect = matches!(
+            response.status(),
+            StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT
+        );
+        let Some(location) = response.headers().get(LOCATION).filter(|_| redirect) else {
+            return Ok(None);
+        };
+
+        let next = location
+            .to_str()
+            .ok()
+            .and_then(|location| response.url().join(location).ok())
+            .ok_or_else(|| {
+                DownloadError::Redirect(format!(
+                    "invalid Location {:?} from {}",
+                    location,
+                    redact::redacted(response.url().as_str())
+                ))
+            })?;
+        self.check(origin, &next, followed + 1)?;
+        Ok(Some(next))
+    }
+
+    /// Fails if a redirect to `next` as hop number `hops` breaks the policy
+    fn check(&self, origin: &Url, next: &Url, hops: usize) -> Result<(), DownloadError> {
+        let refuse = |reason: &str| {
+            Err(DownloadError::Redirect(format!(
+                "{} to {}",
+                reason,
+                redact::redacted(next.as_str())
+            )))
+        };
+
+        if hops > self.max_redirects {
+            return refuse(&format!("more than {} redirects", self.max_redirects));
+        }
+        if !matches!(next.scheme(), "http" | "https") {
+            return refuse("unsupported scheme");
+        }
+        if !self.allow_downgrade && origin.scheme() == "https" && next.scheme() == "http" {
+            return refuse("https downgraded to http");
+        }
+        if !self.allow_cross_host && origin.host_str() != next.host_str() {
+            return refuse("redirect to another host");
+        }
+        Ok(())
+    }
+}
+
+/// Whether `a` and `b` share host and port, so credentials for one may be sent to the other
+pub(crate) fn same_origin(a: &Url, b: &Url) -> bool {
+    a.host_str() == b.host_str() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+/// Whether a header by this name likely carries a secret, such as `X-API-Key` or `X-Auth-Token`
+pub(crate) fn is_credential(name: &HeaderName) -> bool {
+    ["auth", "cookie", "key", "token", "secret", "session"]
+        .iter()
+        .any(|word| name.as_str().contains(word))
+}
+
+/// Drops the headers that must not follow a redirect to another origin: the standard credential
+/// headers and every value marked sensitive
+pub(crate) fn remove_credentials(headers: &mut HeaderMap) {
+    headers.remove(AUTHORIZATION);
+    headers.remove(PROXY_AUTHORIZATION);
+    headers.remove(COOKIE);
+
+    let sensitive: Vec<HeaderName> = headers
+        .iter()
+        .filter(|(_, value)| value.is_sensitive())
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in sensitive {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn policy_limits_redirects() {
+        let origin = url("https://example.com/a.bin");
+        let policy = RedirectPolicy::new().with_max_redirects(2);
+
+        assert!(policy
+            .check(&origin, &url("https://cdn.example.net/a.bin?sig=1"), 2)
+            .is_ok());
+        assert!(policy
+            .check(&origin, &url("https://example.com/b.bin"), 3)
+            .is_err());
+        assert!(policy
+            .check(&origin, &url("http://example.com/a.bin"), 1)
+            .is_err());
+        assert!(policy
+            .check(&origin, &url("ftp://example.com/a.bin"), 1)
+            .is_err());
+
+        let same_host = RedirectPolicy::new()
+            .with_cross_host(false)
+            .with_https_downgrade(true);
+        assert!(same_host
+            .check(&origin, &url("http://example.com/a.bin"), 1)
+            .is_ok());
+        assert!(same_host
+            .check(&origin, &url("https://cdn.example.net/a.bin"), 1)
+            .is_err());
+    }
+
+    #[test]
+    fn credentials_stay_with_their_origin() {
+        let api_key = HeaderName::from_static("x-api-key");
+        assert!(is_credential(&api_key));
+        assert!(!is_credential(&reqwest::header::ACCEPT));
+
+        let mut key = HeaderValue::from_static("k3y");
+        key.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer t"));
+        headers.insert(api_key, key);
+        headers.insert(reqwest::header::ACCEPT, HeaderValue::from_static("*/*"));
+
+        remove_credentials(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert!(headers.contains_key(reqwest::header::ACCEPT));
+    }
+}
@@ -0,0 +1,196 @@
+// retry.rs
+
+use crate::error::DownloadError;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::ErrorKind,
+    time::Duration,
+};
+
+// =====================================
+// RetryPolicy trait
+// =====================================
+
+/// Decides whether and when a failed download attempt is retried
+pub trait RetryPolicy: Send + Sync {
+    /// Total number of attempts, including the first one
+    fn max_attempts(&self) -> usize;
+
+    /// Delay before the next attempt, after `attempt` attempts have failed
+    fn delay(&self, attempt: usize) -> Duration;
+
+    /// Whether another attempt could succeed where this one failed.
+    ///
+    /// By default network timeouts, stalls, connection failures, 5xx, 408 and 429 are retried, while
+    /// other 4xx responses, refused redirects, local IO failures, a full disk and checksum
+    /// mismatches fail fast.
+    fn is_retryable(&self, error: &DownloadError) -> bool {
+        match error {
+            DownloadError::Http(e) => !e.is_builder() && !e.is_redirect(),
+            DownloadError::HttpStatus { code, .. } => {
+                (500..600).contains(code) || *code == 408 || *code == 429
+            }
+            DownloadError::Io(e) => matches!(
+                e.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::BrokenPipe
+            ),
+            DownloadError::Timeout(_)
+            | DownloadError::InvalidResponse(_)
+            | DownloadError::RemoteChanged
+            | DownloadError::Stalled(_)
+            | DownloadError::SizeMismatch { .. } => true,
+            DownloadError::InvalidConfig(_)
+            | DownloadError::Redirect(_)
+            | DownloadError::InvalidRange
+            | DownloadError::RangeNotSatisfiable
+            | DownloadError::UnsupportedServer
+            | DownloadError::DiskFull(_)
+            | DownloadError::Cancelled
+            | DownloadError::LockHeld { .. }
+            | DownloadError::ChecksumMismatch { .. } => false,
+            DownloadError::Context { source, .. } => self.is_retryable(source),
+        }
+    }
+}
+
+// =====================================
+// ExponentialBackoff
+// =====================================
+
+/// Exponential backoff capped at `max_delay`, with optional full jitter
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    max_attempts: usize,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            jitter: true,
+        }
+    }
+}
+
+impl ExponentialBackoff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Delay after the first failure; doubled after each further one
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Picks each delay uniformly between zero and the backoff ceiling
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+}
+
+impl RetryPolicy for ExponentialBackoff {
+    fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    fn delay(&self, attempt: usize) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31) as u32;
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_delay);
+
+        if self.jitter {
+            ceiling.mul_f64(random_fraction())
+        } else {
+            ceiling
+        }
+    }
+}
+
+/// Uniform value in `[0, 1)` from the randomly keyed std hasher
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_cap() {
+        let policy = ExponentialBackoff::new()
+            .with_base_delay(Duration::from_secs(1))
+            .with_max_delay(Duration::from_secs(5))
+            .with_jitter(false);
+
+        let delays: Vec<_> = (1..=5)
+            .map(|attempt| policy.delay(attempt).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+    }
+
+    #[test]
+    fn jittered_delay_stays_below_ceiling() {
+        let policy = ExponentialBackoff::new().with_max_delay(Duration::from_secs(2));
+        for attempt in 1..10 {
+            assert!(policy.delay(attempt) <= Duration::from_secs(2));
+        }
+    }
+
+    #[test]
+    fn local_io_failures_fail_fast() {
+        let policy = ExponentialBackoff::new();
+        let denied = DownloadError::Io(ErrorKind::PermissionDenied.into());
+        let reset = DownloadError::Io(ErrorKind::ConnectionReset.into());
+
+        assert!(!policy.is_retryable(&denied));
+        assert!(policy.is_retryable(&reset));
+        assert!(matches!(
+            DownloadError::from(std::io::Error::from(ErrorKind::StorageFull)),
+            DownloadError::DiskFull(_)
+        ));
+    }
+
+    #[test]
+    fn status_and_context_are_classified() {
+        let policy = ExponentialBackoff::new();
+        let status = |code| DownloadError::HttpStatus {
+            code,
+            url: "http://example.com/file.bin".to_string(),
+        };
+
+        assert!(policy.is_retryable(&status(503)));
+        assert!(policy.is_retryable(&status(429)));
+        assert!(!policy.is_retryable(&status(404)));
+        assert!(!policy.is_retryable(&DownloadError::Cancelled.with_context(
+            "http://example.com/file.bin",
+            std::path::Path::new("file.bin"),
+            1
+        )));
+    }
+}
@@ -0,0 +1,167 @@
+// segment.rs
+
+use serde::{Deserialize, Serialize};
+use std::{fs, io};
+
+// =====================================
+// Segment
+// =====================================
+
+/// A contiguous byte range of the remote file, fetched by one connection
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Segment {
+    /// First byte of the range (inclusive)
+    pub start: u64,
+    /// Last byte of the range (inclusive)
+    pub end: u64,
+    /// Bytes of this range already written to the part file
+    pub downloaded: u64,
+}
+
+impl Segment {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.downloaded >= self.len()
+    }
+
+    /// Absolute offset of the next byte to fetch
+    pub fn next_offset(&self) -> u64 {
+        self.start + self.downloaded
+    }
+}
+
+// =====================================
+// SegmentMap
+// =====================================
+
+/// Split of a remote file into segments, persisted in the part file's metadata
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentMap {
+    pub total: u64,
+    pub segments: Vec<Segment>,
+}
+
+impl SegmentMap {
+    /// Splits `total` bytes into at most `count` near-equal segments
+    pub fn plan(total: u64, count: usize) -> Self {
+        let count = (count.max(1) as u64).min(total.max(1));
+        let base = total / count;
+        let extra = total % count;
+
+        let mut segments = Vec::with_capacity(count as usize);
+        let mut start = 0;
+        for index in 0..count {
+            let len = base + u64::from(index < extra);
+            if len == 0 {
+                break;
+            }
+            segments.push(Segment {
+                start,
+                end: start + len - 1,
+                downloaded: 0,
+            });
+            start += len;
+        }
+
+        Self { total, segments }
+    }
+
+    pub fn downloaded(&self) -> u64 {
+        self.segments.iter().map(|segment| segment.downloaded).sum()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.segments.iter().all(Segment::is_complete)
+    }
+
+    /// Checks that the segments cover `0..total` back to back and that none holds more than its
+    /// length, as a map read back from disk may be damaged or edited
+    pub fn validate(&self) -> Result<(), String> {
+        let mut next = 0;
+        for segment in &self.segments {
+            if segment.start != next || segment.end < segment.start || segment.end >= self.total {
+                return Err(format!(
+                    "segment {}-{} does not continue at byte {} of {}",
+                    segment.start, segment.end, next, self.total
+                ));
+            }
+            if segment.downloaded > segment.len() {
+                return Err(format!(
+                    "segment {}-{} claims {} downloaded bytes",
+                    segment.start, segment.end, segment.downloaded
+                ));
+            }
+            next = segment.end + 1;
+        }
+
+        if next != self.total || self.segments.is_empty() {
+            return Err(format!("segments cover {} of {} bytes", next, self.total));
+        }
+        Ok(())
+    }
+}
+
+// =====================================
+// Positioned writes
+// =====================================
+
+#[cfg(unix)]
+pub fn write_all_at(file: &fs::File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)
+}
+
+#[cfg(windows)]
+pub fn write_all_at(file: &fs::File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        let written = file.seek_write(buf, offset)?;
+        if written == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[written..];
+        offset += written as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_covers_whole_file() {
+        let map = SegmentMap::plan(10, 3);
+        let ranges: Vec<_> = map.segments.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(ranges, vec![(0, 3), (4, 6), (7, 9)]);
+    }
+
+    #[test]
+    fn plan_never_creates_empty_segments() {
+        assert_eq!(SegmentMap::plan(2, 8).segments.len(), 2);
+        assert!(SegmentMap::plan(0, 4).segments.is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_damaged_maps() {
+        assert!(SegmentMap::plan(10, 3).validate().is_ok());
+
+        let broken = |edit: fn(&mut SegmentMap)| {
+            let mut map = SegmentMap::plan(10, 3);
+            edit(&mut map);
+            map.validate().is_err()
+        };
+        assert!(broken(|map| map.segments.clear()));
+        assert!(broken(|map| map.segments[1].end = 2));
+        assert!(broken(|map| map.segments[2].start = 8));
+        assert!(broken(|map| map.total = 20));
+        assert!(broken(|map| map.segments[0].downloaded = 5));
+    }
+}
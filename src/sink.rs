@@ -0,0 +1,237 @@
+// sink.rs
+
+use crate::writer::{FileTasks, FileWriter};
+use bytes::{Bytes, BytesMut};
+use futures::future::{self, BoxFuture, FutureExt};
+use std::{
+    fs::File,
+    io::{self, ErrorKind},
+    sync::{Arc, Mutex},
+};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+// =====================================
+// Sink trait
+// =====================================
+
+/// Destination of the downloaded bytes, which arrive in order.
+///
+/// A retry continues after the [`Sink::written`] bytes when the server can resume there, and
+/// calls [`Sink::restart`] when it has to send the file from the first byte again.
+pub trait Sink: Send {
+    /// Bytes written since the start or the last restart
+    fn written(&self) -> u64;
+
+    /// Appends `chunk` to what was written so far; a write that fails may have taken part of it,
+    /// which [`Sink::written`] then counts
+    fn write(&mut self, chunk: Bytes) -> BoxFuture<'_, io::Result<()>>;
+
+    /// Discards everything written so far; a sink that cannot fails with `ErrorKind::Unsupported`
+    fn restart(&mut self) -> BoxFuture<'_, io::Result<()>>;
+
+    /// Flushes what was written, after every attempt
+    fn finish(&mut self) -> BoxFuture<'_, io::Result<()>>;
+}
+
+// =====================================
+// MemorySink
+// =====================================
+
+/// Collects the download in memory; clones share the same buffer
+#[derive(Clone, Debug, Default)]
+pub struct MemorySink {
+    buffer: Arc<Mutex<BytesMut>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the bytes collected so far, leaving the buffer empty
+    pub fn take(&self) -> Bytes {
+        self.buffer.lock().unwrap().split().freeze()
+    }
+}
+
+impl Sink for MemorySink {
+    fn written(&self) -> u64 {
+        self.buffer.lock().unwrap().len() as u64
+    }
+
+    fn write(&mut self, chunk: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        self.buffer.lock().unwrap().extend_from_slice(&chunk);
+        future::ready(Ok(())).boxed()
+    }
+
+    fn restart(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        self.buffer.lock().unwrap().clear();
+        future::ready(Ok(())).boxed()
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        future::ready(Ok(())).boxed()
+    }
+}
+
+// =====================================
+// WriterSink
+// =====================================
+
+/// Streams the download into an `AsyncWrite` such as stdout, a socket or a compressor.
+///
+/// Written bytes cannot be taken back, so a retry that has to start over fails instead.
+#[derive(Debug)]
+pub struct WriterSink<W> {
+    writer: W,
+    written: u64,
+}
+
+impl<W: AsyncWrite + Unpin + Send> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, written: 0 }
+    }
+}
+
+impl WriterSink<tokio::io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(tokio::io::stdout())
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> Sink for WriterSink<W> {
+    fn written(&self) -> u64 {
+        self.written
+    }
+
+    fn write(&mut self, chunk: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        async move {
+            // Counted as the writer takes them, as bytes it took cannot be sent again
+            let mut rest = &chunk[..];
+            while !rest.is_empty() {
+                let taken = self.writer.write(rest).await?;
+                if taken == 0 {
+                    return Err(ErrorKind::WriteZero.into());
+                }
+                self.written += taken as u64;
+                rest = &rest[taken..];
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn restart(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        let result = if self.written == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                ErrorKind::Unsupported,
+                format!(
+                    "the server restarted the file, but {} bytes were already written to the sink",
+                    self.written
+                ),
+            ))
+        };
+        future::ready(result).boxed()
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        self.writer.flush().boxed()
+    }
+}
+
+// =====================================
+// FileSink
+// =====================================
+
+/// Appends to a part file through a [`FileWriter`], for downloads into a file
+pub(crate) struct FileSink {
+    file: File,
+    writer: Option<FileWriter>,
+    buffer_size: usize,
+    tasks: FileTasks,
+    written: u64,
+}
+
+impl FileSink {
+    /// Sink over `file`, opened for appending, which already holds `written` bytes; its writer
+    /// runs as one of `tasks`
+    pub fn new(file: File, written: u64, buffer_size: usize, tasks: FileTasks) -> io::Result<Self> {
+        let writer = FileWriter::spawn(file.try_clone()?, buffer_size, &tasks);
+        Ok(Self {
+            file,
+            writer: Some(writer),
+            buffer_size,
+            tasks,
+            written,
+        })
+    }
+}
+
+impl Sink for FileSink {
+    fn written(&self) -> u64 {
+        self.written
+    }
+
+    fn write(&mut self, chunk: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        async move {
+            let len = chunk.len() as u64;
+            match self.writer {
+                Some(ref mut writer) => writer.write(chunk).await?,
+                None => return Err(io::Error::other("File sink already finished")),
+            }
+            self.written += len;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn restart(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        async move {
+            self.finish().await?;
+            self.file.set_len(0)?;
+            self.writer = Some(FileWriter::spawn(
+                self.file.try_clone()?,
+                self.buffer_size,
+                &self.tasks,
+            ));
+            self.written = 0;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        async move {
+            match self.writer.take() {
+                Some(writer) => writer.finish().await,
+                None => Ok(()),
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_memory_can_restart() {
+        let memory = MemorySink::new();
+        let mut sink = memory.clone();
+        sink.write(Bytes::from_static(b"stale")).await.unwrap();
+        sink.restart().await.unwrap();
+        sink.write(Bytes::from_static(b"fresh")).await.unwrap();
+        assert_eq!(sink.written(), 5);
+        assert_eq!(memory.take(), Bytes::from_static(b"fresh"));
+
+        let mut writer = WriterSink::new(Vec::new());
+        writer.restart().await.unwrap();
+        writer.write(Bytes::from_static(b"sent")).await.unwrap();
+        let error = writer.restart().await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Unsupported);
+        assert_eq!(writer.writer, b"sent");
+    }
+}
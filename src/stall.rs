@@ -0,0 +1,180 @@
+// stall.rs
+
+use crate::error::DownloadError;
+use futures::{Stream, StreamExt};
+use std::time::{Duration, Instant};
+
+/// Smallest wait between two stall checks while no data arrives
+const MIN_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Throughput a transfer must sustain over every `window`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinThroughput {
+    pub bytes_per_second: u64,
+    pub window: Duration,
+}
+
+/// Rules that abort an attempt whose connection is alive but not delivering data
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StallConfig {
+    /// Longest time without receiving a single byte
+    pub idle_timeout: Option<Duration>,
+    pub min_throughput: Option<MinThroughput>,
+}
+
+/// Reads a body stream while enforcing a [`StallConfig`]
+pub(crate) struct StallDetector {
+    config: StallConfig,
+    last_byte: Instant,
+    window_start: Instant,
+    window_bytes: u64,
+}
+
+impl StallDetector {
+    pub fn new(config: StallConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            config,
+            last_byte: now,
+            window_start: now,
+            window_bytes: 0,
+        }
+    }
+
+    /// Next chunk of `stream`, or [`DownloadError::Stalled`] once a rule is broken
+    pub async fn next<S, T>(&mut self, stream: &mut S) -> Result<Option<T>, DownloadError>
+    where
+        S: Stream<Item = Result<T, reqwest::Error>> + Unpin,
+        T: AsRef<[u8]>,
+    {
+        loop {
+            let item = match self.time_to_next_check() {
+                Some(wait) => match tokio::time::timeout(wait, stream.next()).await {
+                    Ok(item) => item,
+                    Err(_) => {
+                        self.check()?;
+                        continue;
+                    }
+                },
+                None => stream.next().await,
+            };
+
+            let chunk = item.transpose()?;
+            if let Some(ref chunk) = chunk {
+                let len = chunk.as_ref().len() as u64;
+                if len > 0 {
+                    self.last_byte = Instant::now();
+                }
+                self.window_bytes += len;
+            }
+            self.check()?;
+            return Ok(chunk);
+        }
+    }
+
+    /// Starts measuring afresh, e.g. after reading was paused on purpose
+    pub fn reset(&mut self) {
+        *self = Self::new(self.config);
+    }
+
+    /// Leaves `paused` out of both rules, e.g. time spent waiting on a rate limiter, while keeping
+    /// the bytes counted in the current window
+    pub fn exclude(&mut self, paused: Duration) {
+        self.last_byte += paused;
+        self.window_start += paused;
+    }
+
+    fn check(&mut self) -> Result<(), DownloadError> {
+        if let Some(idle_timeout) = self.config.idle_timeout {
+            if self.last_byte.elapsed() >= idle_timeout {
+                return Err(DownloadError::Stalled(format!(
+                    "no data received for {:.1}s",
+                    idle_timeout.as_secs_f64()
+                )));
+            }
+        }
+
+        if let Some(min) = self.config.min_throughput {
+            let elapsed = self.window_start.elapsed();
+            if elapsed >= min.window {
+                let rate = self.window_bytes as f64 / elapsed.as_secs_f64();
+                if rate < min.bytes_per_second as f64 {
+                    return Err(DownloadError::Stalled(format!(
+                        "{:.0} B/s over {:.1}s is below the minimum of {} B/s",
+                        rate,
+                        elapsed.as_secs_f64(),
+                        min.bytes_per_second
+                    )));
+                }
+                self.window_start = Instant::now();
+                self.window_bytes = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn time_to_next_check(&self) -> Option<Duration> {
+        let idle = self
+            .config
+            .idle_timeout
+            .map(|timeout| timeout.saturating_sub(self.last_byte.elapsed()));
+        let window = self
+            .config
+            .min_throughput
+            .map(|min| min.window.saturating_sub(self.window_start.elapsed()));
+
+        match (idle, window) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+        .map(|wait| wait.max(MIN_CHECK_INTERVAL))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn slow_stream_breaks_min_throughput() {
+        let mut stream = Box::pin(futures::stream::unfold((), |_| async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Some((Ok::<_, reqwest::Error>(vec![0u8; 1]), ()))
+        }));
+        let mut detector = StallDetector::new(StallConfig {
+            idle_timeout: None,
+            min_throughput: Some(MinThroughput {
+                bytes_per_second: 1000,
+                window: Duration::from_millis(100),
+            }),
+        });
+
+        let err = loop {
+            if let Err(e) = detector.next(&mut stream).await {
+                break e;
+            }
+        };
+        assert!(matches!(err, DownloadError::Stalled(_)));
+    }
+
+    #[tokio::test]
+    async fn excluded_pauses_do_not_count() {
+        let mut stream = Box::pin(futures::stream::iter(
+            (0..5).map(|_| Ok::<_, reqwest::Error>(vec![0u8; 100])),
+        ));
+        let mut detector = StallDetector::new(StallConfig {
+            idle_timeout: Some(Duration::from_millis(50)),
+            min_throughput: Some(MinThroughput {
+                bytes_per_second: 10_000,
+                window: Duration::from_millis(50),
+            }),
+        });
+
+        while detector.next(&mut stream).await.unwrap().is_some() {
+            let started = Instant::now();
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            detector.exclude(started.elapsed());
+        }
+    }
+}
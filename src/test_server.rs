@@ -0,0 +1,366 @@
+// test_server.rs
+//
+// Minimal HTTP/1.1 file server used by unit tests, so they do not depend on the network.
+
+use openssl::{
+    pkey::PKey,
+    ssl::{Ssl, SslAcceptor, SslMethod, SslVerifyMode},
+    x509::{store::X509StoreBuilder, X509},
+};
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+};
+use tokio_openssl::SslStream;
+
+pub struct TestServer {
+    pub url: String,
+}
+
+#[derive(Clone)]
+pub struct ServerOptions {
+    pub body: Arc<Vec<u8>>,
+    pub etag: Option<String>,
+    pub content_disposition: Option<String>,
+    /// Headers a request must carry, or it is answered with 401
+    pub required_headers: Vec<(String, String)>,
+    /// The first `count` responses hang forever after sending `bytes` of their body
+    pub stall: Option<(usize, Arc<AtomicUsize>)>,
+    /// The first `count` responses end cleanly after `bytes` of their body, headers unchanged
+    /// except for `Content-Length`
+    pub truncate: Option<(usize, Arc<AtomicUsize>)>,
+    /// Answers every request with 200 and the full body
+    pub ignore_range: bool,
+    /// Partial responses start this many bytes after the requested offset
+    pub range_skew: u64,
+    /// Partial responses claim the file is this long
+    pub claimed_total: Option<u64>,
+    /// Sends the body `bytes` at a time with a pause after each piece
+    pub throttle: Option<(usize, Duration)>,
+    /// Answers every request for the file with a 302 to this URL
+    pub redirect: Option<String>,
+    /// Sends full responses chunked, without `Content-Length`
+    pub chunked: bool,
+    /// From the `count`-th request for the file on, it carries this ETag instead
+    pub revision: Option<(usize, String)>,
+    /// Requests for the file received so far
+    pub hits: Arc<AtomicUsize>,
+}
+
+impl ServerOptions {
+    pub fn new(body: Vec<u8>) -> Self {
+        Self {
+            body: Arc::new(body),
+            etag: None,
+            content_disposition: None,
+            required_headers: Vec::new(),
+            stall: None,
+            truncate: None,
+            ignore_range: false,
+            range_skew: 0,
+            claimed_total: None,
+            throttle: None,
+            redirect: None,
+            chunked: false,
+            revision: None,
+            hits: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn stall_after(mut self, bytes: usize, count: usize) -> Self {
+        self.stall = Some((bytes, Arc::new(AtomicUsize::new(count))));
+        self
+    }
+
+    pub fn truncate_after(mut self, bytes: usize, count: usize) -> Self {
+        self.truncate = Some((bytes, Arc::new(AtomicUsize::new(count))));
+        self
+    }
+
+    pub fn ignore_range(mut self) -> Self {
+        self.ignore_range = true;
+        self
+    }
+
+    pub fn claim_total(mut self, total: u64) -> Self {
+        self.claimed_total = Some(total);
+        self
+    }
+
+    pub fn chunked(mut self) -> Self {
+        self.chunked = true;
+        self
+    }
+
+    pub fn range_skew(mut self, skew: u64) -> Self {
+        self.range_skew = skew;
+        self
+    }
+
+    pub fn redirect_to(mut self, url: &str) -> Self {
+        self.redirect = Some(url.to_string());
+        self
+    }
+
+    pub fn throttle(mut self, bytes: usize, pause: Duration) -> Self {
+        self.throttle = Some((bytes, pause));
+        self
+    }
+
+    pub fn require_header(mut self, name: &str, value: &str) -> Self {
+        self.required_headers
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn content_disposition(mut self, value: &str) -> Self {
+        self.content_disposition = Some(value.to_string());
+        self
+    }
+
+    pub fn etag(mut self, etag: &str) -> Self {
+        self.etag = Some(etag.to_string());
+        self
+    }
+
+    pub fn revise_after(mut self, count: usize, etag: &str) -> Self {
+        self.revision = Some((count, etag.to_string()));
+        self
+    }
+}
+
+/// Deterministic payload of `len` bytes
+pub fn payload(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+/// Unique path in the temp directory for a test's output file
+pub fn temp_output(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("resumable_downloader_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+impl TestServer {
+    pub async fn start(options: ServerOptions) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/file.bin", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, options.clone()));
+            }
+        });
+
+        Self { url }
+    }
+
+    /// Serves over TLS as `localhost`, with the certificate in `testdata/tls` signed by `ca.pem`.
+    ///
+    /// Handshakes fail unless the client presents a certificate that `ca.pem` signed.
+    pub async fn start_tls(options: ServerOptions) -> Self {
+        let ca = X509::from_pem(include_bytes!("../testdata/tls/ca.pem")).unwrap();
+        let mut trusted = X509StoreBuilder::new().unwrap();
+        trusted.add_cert(ca.clone()).unwrap();
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor
+            .set_certificate(&X509::from_pem(include_bytes!("../testdata/tls/server.pem")).unwrap())
+            .unwrap();
+        acceptor
+            .set_private_key(
+                &PKey::private_key_from_pem(include_bytes!("../testdata/tls/server-key.pem"))
+                    .unwrap(),
+            )
+            .unwrap();
+        acceptor.add_client_ca(&ca).unwrap();
+        acceptor.set_verify_cert_store(trusted.build()).unwrap();
+        acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "https://localhost:{}/file.bin",
+            listener.local_addr().unwrap().port()
+        );
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(ssl) = Ssl::new(acceptor.context()) else {
+                    continue;
+                };
+                let options = options.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = SslStream::new(ssl, stream) else {
+                        return;
+                    };
+                    if Pin::new(&mut stream).accept().await.is_ok() {
+                        serve(stream, options).await;
+                    }
+                });
+            }
+        });
+
+        Self { url }
+    }
+}
+
+async fn serve<S>(mut stream: S, options: ServerOptions)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request).to_string();
+    if !request.starts_with("GET /file.bin ") && !request.starts_with("GET /file.bin?") {
+        let _ = stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+            .await;
+        return;
+    }
+    let served = options.hits.fetch_add(1, Ordering::SeqCst);
+    let etag = match options.revision {
+        Some((count, ref etag)) if served >= count => Some(etag.as_str()),
+        _ => options.etag.as_deref(),
+    };
+    if let Some(ref location) = options.redirect {
+        let head = format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            location
+        );
+        let _ = stream.write_all(head.as_bytes()).await;
+        return;
+    }
+    let header = |wanted: &str| {
+        request.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case(wanted)
+                .then(|| value.trim().to_string())
+        })
+    };
+
+    let authorized = options
+        .required_headers
+        .iter()
+        .all(|(name, value)| header(name).as_deref() == Some(value.as_str()));
+    if !authorized {
+        let _ = stream
+            .write_all(
+                b"HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            )
+            .await;
+        return;
+    }
+
+    let total = options.body.len() as u64;
+    let if_range_matches = match header("if-range") {
+        Some(if_range) => etag == Some(if_range.as_str()),
+        None => true,
+    };
+    let range = header("range")
+        .filter(|_| if_range_matches && !options.ignore_range)
+        .map(|range| range.trim_start_matches("bytes=").to_string())
+        .and_then(|range| {
+            let (start, end) = range.split_once('-')?;
+            let start = start.parse::<u64>().ok()? + options.range_skew;
+            let end = end.parse().unwrap_or(total.saturating_sub(1));
+            Some((start, end.min(total.saturating_sub(1))))
+        });
+
+    let (status, mut headers, body) = match range {
+        Some((start, _)) if start >= total => (
+            "416 Range Not Satisfiable",
+            format!("Content-Range: bytes */{}\r\n", total),
+            &[][..],
+        ),
+        Some((start, end)) => (
+            "206 Partial Content",
+            format!(
+                "Content-Range: bytes {}-{}/{}\r\n",
+                start,
+                end,
+                options.claimed_total.unwrap_or(total)
+            ),
+            &options.body[start as usize..=end as usize],
+        ),
+        None => ("200 OK", String::new(), &options.body[..]),
+    };
+    let body = match options.truncate {
+        Some((bytes, ref remaining)) if take_one(remaining) => &body[..bytes.min(body.len())],
+        _ => body,
+    };
+    if let Some(etag) = etag {
+        headers.push_str(&format!("ETag: {}\r\n", etag));
+    }
+    if let Some(ref disposition) = options.content_disposition {
+        headers.push_str(&format!("Content-Disposition: {}\r\n", disposition));
+    }
+
+    let chunked = options.chunked && range.is_none();
+    let length = if chunked {
+        "Transfer-Encoding: chunked\r\n".to_string()
+    } else {
+        format!("Content-Length: {}\r\n", body.len())
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\n{}{}Connection: close\r\n\r\n",
+        status, length, headers
+    );
+    let _ = stream.write_all(head.as_bytes()).await;
+
+    if chunked {
+        if !body.is_empty() {
+            let _ = stream
+                .write_all(format!("{:x}\r\n", body.len()).as_bytes())
+                .await;
+            let _ = stream.write_all(body).await;
+            let _ = stream.write_all(b"\r\n").await;
+        }
+        let _ = stream.write_all(b"0\r\n\r\n").await;
+        let _ = stream.shutdown().await;
+        return;
+    }
+
+    if let Some((bytes, ref remaining)) = options.stall {
+        if take_one(remaining) {
+            let _ = stream.write_all(&body[..bytes.min(body.len())]).await;
+            let _ = stream.flush().await;
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            return;
+        }
+    }
+
+    if let Some((bytes, pause)) = options.throttle {
+        for piece in body.chunks(bytes) {
+            if stream.write_all(piece).await.is_err() || stream.flush().await.is_err() {
+                return;
+            }
+            tokio::time::sleep(pause).await;
+        }
+    } else {
+        let _ = stream.write_all(body).await;
+    }
+    let _ = stream.shutdown().await;
+}
+
+/// Decrements `remaining`; returns false once it has reached zero
+fn take_one(remaining: &AtomicUsize) -> bool {
+    remaining
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok()
+}
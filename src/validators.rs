@@ -0,0 +1,97 @@
+// validators.rs
+
+use reqwest::header::{CONTENT_RANGE, ETAG, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+
+/// Identifies the remote version a part file was downloaded from
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// Size of the complete remote file
+    pub total: Option<u64>,
+}
+
+impl Validators {
+    /// Captures validators from a response whose body starts at byte `offset`
+    pub fn from_response(response: &reqwest::Response, offset: u64) -> Self {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        let total = match header(CONTENT_RANGE) {
+            Some(content_range) => content_range
+                .rsplit('/')
+                .next()
+                .and_then(|total| total.parse().ok()),
+            None if response.status() == reqwest::StatusCode::OK => response.content_length(),
+            None => response.content_length().map(|len| len + offset),
+        };
+
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            total,
+        }
+    }
+
+    /// Value for an `If-Range` header; weak ETags are not allowed there
+    pub fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    /// True when nothing known about the two versions contradicts each other
+    pub fn is_compatible(&self, other: &Validators) -> bool {
+        fn agree<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
+            }
+        }
+
+        agree(&self.etag, &other.etag)
+            && agree(&self.last_modified, &other.last_modified)
+            && agree(&self.total, &other.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_range_skips_weak_etags() {
+        let validators = Validators {
+            etag: Some("W/\"abc\"".to_string()),
+            last_modified: Some("Tue, 15 Nov 1994 12:45:26 GMT".to_string()),
+            total: None,
+        };
+        assert_eq!(validators.if_range(), Some("Tue, 15 Nov 1994 12:45:26 GMT"));
+    }
+
+    #[test]
+    fn unknown_fields_are_compatible() {
+        let saved = Validators {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            total: Some(10),
+        };
+        let fresh = Validators {
+            etag: None,
+            last_modified: Some("x".to_string()),
+            total: Some(10),
+        };
+        assert!(saved.is_compatible(&fresh));
+        assert!(!saved.is_compatible(&Validators {
+            total: Some(11),
+            ..fresh
+        }));
+    }
+}
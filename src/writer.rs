@@ -0,0 +1,176 @@
+// writer.rs
+
+use bytes::Bytes;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    sync::{mpsc, Notify},
+    task::JoinHandle,
+};
+
+/// Default size of the buffer that coalesces chunks into larger writes
+pub const DEFAULT_WRITE_BUFFER: usize = 256 * 1024;
+
+/// Chunks that may wait for the writer before the reader is slowed down
+const QUEUE_DEPTH: usize = 32;
+
+// =====================================
+// FileTasks
+// =====================================
+
+/// Blocking tasks that write a download's files, counted so they can be waited for.
+///
+/// Dropping the future that awaits such a task does not stop it, so a cancelled attempt waits
+/// for [`FileTasks::idle`] before it gives up the lock.
+#[derive(Clone, Default)]
+pub(crate) struct FileTasks {
+    inner: Arc<TasksInner>,
+}
+
+#[derive(Default)]
+struct TasksInner {
+    running: AtomicUsize,
+    idle: Notify,
+}
+
+/// Counts a task as finished when dropped, whether it ran, panicked or never started
+struct Running(Arc<TasksInner>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl FileTasks {
+    /// Runs `f` on the blocking pool
+    pub fn spawn<F, T>(&self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.inner.running.fetch_add(1, Ordering::SeqCst);
+        let running = Running(self.inner.clone());
+        tokio::task::spawn_blocking(move || {
+            let _running = running;
+            f()
+        })
+    }
+
+    /// Runs `f` on the blocking pool and waits for its result
+    pub async fn run<F, T>(&self, f: F) -> io::Result<T>
+    where
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.spawn(f).await.map_err(io::Error::other)?
+    }
+
+    /// Waits until no task is running
+    pub async fn idle(&self) {
+        loop {
+            // Registered before looking, so a task ending in between is not missed
+            let notified = self.inner.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.inner.running.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+// =====================================
+// FileWriter
+// =====================================
+
+/// Appends chunks to a file from a blocking task, off the async runtime.
+///
+/// The queue is bounded, so a slow disk throttles the network reader instead of buffering
+/// without limit. Call [`FileWriter::finish`] to flush and learn whether every write succeeded.
+pub(crate) struct FileWriter {
+    sender: mpsc::Sender<Bytes>,
+    task: JoinHandle<io::Result<()>>,
+}
+
+impl FileWriter {
+    /// Starts the writer as one of `tasks`, which keeps counting it if the writer is dropped
+    pub fn spawn(file: File, buffer_size: usize, tasks: &FileTasks) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<Bytes>(QUEUE_DEPTH);
+        let task = tasks.spawn(move || {
+            let mut writer = BufWriter::with_capacity(buffer_size, file);
+            while let Some(chunk) = receiver.blocking_recv() {
+                writer.write_all(&chunk)?;
+            }
+            writer.flush()
+        });
+
+        Self { sender, task }
+    }
+
+    /// Queues `chunk`, waiting while the queue is full
+    pub async fn write(&mut self, chunk: Bytes) -> io::Result<()> {
+        if self.sender.send(chunk).await.is_err() {
+            // The task only hangs up after a failed write; `finish` reports it
+            return Err(io::Error::other("File writer stopped unexpectedly"));
+        }
+        Ok(())
+    }
+
+    /// Flushes everything queued and waits for the writer to exit
+    pub async fn finish(self) -> io::Result<()> {
+        drop(self.sender);
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn writes_arrive_in_order() {
+        let path = crate::test_server::temp_output("writer.bin");
+        let file = File::create(&path).unwrap();
+
+        let tasks = FileTasks::default();
+        let mut writer = FileWriter::spawn(file, 1000, &tasks);
+        let mut expected = Vec::new();
+        for i in 0..100u8 {
+            let chunk = vec![i; 37];
+            expected.extend_from_slice(&chunk);
+            writer.write(Bytes::from(chunk)).await.unwrap();
+        }
+        writer.finish().await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn dropped_writer_is_waited_for() {
+        let path = crate::test_server::temp_output("writer_dropped.bin");
+        let file = File::create(&path).unwrap();
+
+        let tasks = FileTasks::default();
+        let mut writer = FileWriter::spawn(file, 1000, &tasks);
+        for i in 0..10u8 {
+            writer.write(Bytes::from(vec![i; 300])).await.unwrap();
+        }
+        drop(writer);
+        tasks.idle().await;
+
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 3000);
+    }
+}